@@ -0,0 +1,299 @@
+use eyre::{Report, eyre};
+use std::{
+    ffi::OsStr,
+    io::{BufRead, BufReader, Result, Write},
+    path::Path,
+};
+
+/// Compression formats read on input and written on output
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Compression {
+    Gzip,
+    Zstd,
+    Bzip2,
+}
+
+impl Compression {
+    /// File extension appended to compressed output files
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Gzip => "gz",
+            Self::Zstd => "zst",
+            Self::Bzip2 => "bz2",
+        }
+    }
+
+    /// Recognizes a format from the first bytes of a stream
+    pub fn from_magic(header: &[u8]) -> Option<Self> {
+        match header {
+            [0x1f, 0x8b, ..] => Some(Self::Gzip),
+            [0x28, 0xb5, 0x2f, 0xfd, ..] => Some(Self::Zstd),
+            [b'B', b'Z', b'h', ..] => Some(Self::Bzip2),
+            _ => None,
+        }
+    }
+
+    /// Recognizes a format from a `.gz`, `.zst` or `.bz2` file extension
+    pub fn from_extension(path: &Path) -> Option<Self> {
+        match path.extension().and_then(OsStr::to_str)? {
+            "gz" | "gzip" => Some(Self::Gzip),
+            "zst" | "zstd" => Some(Self::Zstd),
+            "bz2" | "bzip2" => Some(Self::Bzip2),
+            _ => None,
+        }
+    }
+
+    /// Wraps `reader` in a decoder for this format
+    pub fn decoder<'a, R: BufRead + Send + 'a>(
+        self,
+        reader: R,
+    ) -> Result<Box<dyn BufRead + Send + 'a>> {
+        Ok(match self {
+            Self::Gzip => Box::new(BufReader::new(flate2::bufread::MultiGzDecoder::new(reader))),
+            Self::Zstd => Box::new(BufReader::new(zstd::Decoder::with_buffer(reader)?)),
+            Self::Bzip2 => Box::new(BufReader::new(bzip2::bufread::MultiBzDecoder::new(reader))),
+        })
+    }
+
+    /// Wraps `writer` in an encoder for this format. The stream is only complete once
+    /// [`Encoder::try_finish`] has been called.
+    pub fn encoder<W: Write>(self, writer: W, level: Option<i32>) -> Result<Encoder<W>> {
+        Ok(match self {
+            Self::Gzip => {
+                let level =
+                    level.map_or_else(Default::default, |l| flate2::Compression::new(l as u32));
+                Encoder::Gzip(flate2::write::GzEncoder::new(writer, level))
+            }
+            Self::Zstd => Encoder::Zstd(zstd::Encoder::new(writer, level.unwrap_or(0))?),
+            Self::Bzip2 => {
+                let level =
+                    level.map_or_else(Default::default, |l| bzip2::Compression::new(l as u32));
+                Encoder::Bzip2(bzip2::write::BzEncoder::new(writer, level))
+            }
+        })
+    }
+
+    fn levels(self) -> std::ops::RangeInclusive<i32> {
+        match self {
+            Self::Gzip => 0..=9,
+            Self::Zstd => zstd::compression_level_range(),
+            Self::Bzip2 => 1..=9,
+        }
+    }
+}
+
+impl std::str::FromStr for Compression {
+    type Err = Report;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "gzip" | "gz" => Ok(Self::Gzip),
+            "zstd" | "zst" => Ok(Self::Zstd),
+            "bzip2" | "bz2" => Ok(Self::Bzip2),
+            s => Err(eyre!(
+                "Unknown compression `{s}`, expected gzip, zstd or bzip2"
+            )),
+        }
+    }
+}
+
+/// A compression format for output with an optional level, parsed from `<algo>[:level]`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Compressor {
+    pub compression: Compression,
+    pub level: Option<i32>,
+}
+
+impl Compressor {
+    pub fn new(compression: Compression) -> Self {
+        Self {
+            compression,
+            level: None,
+        }
+    }
+
+    /// Picks a compressor from a `.gz`, `.zst` or `.bz2` output file extension
+    pub fn from_extension(path: &Path) -> Option<Self> {
+        Compression::from_extension(path).map(Self::new)
+    }
+
+    pub fn encoder<W: Write>(self, writer: W) -> Result<Encoder<W>> {
+        self.compression.encoder(writer, self.level)
+    }
+}
+
+/// Writes to `W` either as it is or through a compression encoder
+pub enum Encoder<W: Write> {
+    Plain(W),
+    Gzip(flate2::write::GzEncoder<W>),
+    Zstd(zstd::Encoder<'static, W>),
+    Bzip2(bzip2::write::BzEncoder<W>),
+}
+
+impl<W: Write> Encoder<W> {
+    /// Compresses `writer` when a compressor is given
+    pub fn new(writer: W, compressor: Option<Compressor>) -> Result<Self> {
+        match compressor {
+            Some(compressor) => compressor.encoder(writer),
+            None => Ok(Self::Plain(writer)),
+        }
+    }
+
+    /// Writes the end of the compressed stream and flushes it, reporting any error instead
+    /// of losing it on drop. Nothing can be written afterwards.
+    pub fn try_finish(&mut self) -> Result<()> {
+        match self {
+            Self::Plain(_) => {}
+            Self::Gzip(encoder) => encoder.try_finish()?,
+            Self::Zstd(encoder) => encoder.do_finish()?,
+            Self::Bzip2(encoder) => encoder.try_finish()?,
+        }
+        self.get_mut().flush()
+    }
+
+    /// Finishes the stream and returns the writer underneath
+    pub fn finish(mut self) -> Result<W> {
+        self.try_finish()?;
+        Ok(match self {
+            Self::Plain(writer) => writer,
+            Self::Gzip(encoder) => encoder.finish()?,
+            Self::Zstd(encoder) => encoder.finish()?,
+            Self::Bzip2(encoder) => encoder.finish()?,
+        })
+    }
+
+    fn get_mut(&mut self) -> &mut W {
+        match self {
+            Self::Plain(writer) => writer,
+            Self::Gzip(encoder) => encoder.get_mut(),
+            Self::Zstd(encoder) => encoder.get_mut(),
+            Self::Bzip2(encoder) => encoder.get_mut(),
+        }
+    }
+}
+
+impl<W: Write> Write for Encoder<W> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        match self {
+            Self::Plain(writer) => writer.write(buf),
+            Self::Gzip(encoder) => encoder.write(buf),
+            Self::Zstd(encoder) => encoder.write(buf),
+            Self::Bzip2(encoder) => encoder.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        match self {
+            Self::Plain(writer) => writer.flush(),
+            Self::Gzip(encoder) => encoder.flush(),
+            Self::Zstd(encoder) => encoder.flush(),
+            Self::Bzip2(encoder) => encoder.flush(),
+        }
+    }
+}
+
+impl std::str::FromStr for Compressor {
+    type Err = Report;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let (algo, level) = match s.split_once(':') {
+            Some((algo, level)) => (algo, Some(level.parse::<i32>()?)),
+            None => (s, None),
+        };
+        let compression = algo.parse::<Compression>()?;
+        if let Some(level) = level.filter(|level| !compression.levels().contains(level)) {
+            return Err(eyre!("Invalid {compression:?} level {level}"));
+        }
+        Ok(Self { compression, level })
+    }
+}
+
+/// Decompresses `reader` on the fly when it is compressed, detected by magic bytes first
+/// and by the extension of `path` second. Uncompressed input is passed through unchanged.
+pub fn decompress<'a, R: BufRead + Send + 'a>(
+    mut reader: R,
+    path: Option<&Path>,
+) -> Result<Box<dyn BufRead + Send + 'a>> {
+    let compression =
+        Compression::from_magic(reader.fill_buf()?).or(path.and_then(Compression::from_extension));
+    match compression {
+        Some(compression) => {
+            log::debug!("Decompressing {compression:?} input");
+            compression.decoder(reader)
+        }
+        None => Ok(Box::new(reader)),
+    }
+}
+
+/// Names an entry by its file stem, ignoring any compression extension,
+/// so `alpha.json.gz` is named `alpha`
+pub fn entry_name(path: &Path) -> Option<&str> {
+    let path = match Compression::from_extension(path) {
+        Some(_) => Path::new(path.file_stem()?),
+        None => path,
+    };
+    path.file_stem()?.to_str()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Read, Write};
+
+    #[test]
+    fn decompress_by_magic() -> Result<()> {
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::fast());
+        encoder.write_all(b"{\"alpha\":1}")?;
+        let compressed = encoder.finish()?;
+
+        let mut content = String::new();
+        decompress(compressed.as_slice(), None)?.read_to_string(&mut content)?;
+        assert_eq!(content, "{\"alpha\":1}");
+        Ok(())
+    }
+
+    #[test]
+    fn passthrough_plain() -> Result<()> {
+        let mut content = String::new();
+        decompress(b"{}".as_slice(), Some(Path::new("plain.json")))?
+            .read_to_string(&mut content)?;
+        assert_eq!(content, "{}");
+        Ok(())
+    }
+
+    #[test]
+    fn round_trip_encoders() -> Result<()> {
+        for compressor in ["gzip:1", "zstd", "bz2:9"] {
+            let compressor: Compressor = compressor.parse().unwrap();
+            let mut compressed = Vec::new();
+            let mut encoder = compressor.encoder(&mut compressed)?;
+            encoder.write_all(b"{\"alpha\":1}")?;
+            encoder.finish()?;
+
+            let mut content = String::new();
+            decompress(compressed.as_slice(), None)?.read_to_string(&mut content)?;
+            assert_eq!(content, "{\"alpha\":1}");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn parse_compressor() {
+        assert_eq!(
+            "gz:9".parse::<Compressor>().unwrap(),
+            Compressor {
+                compression: Compression::Gzip,
+                level: Some(9)
+            }
+        );
+        assert!("gzip:10".parse::<Compressor>().is_err());
+        assert!("lz4".parse::<Compressor>().is_err());
+    }
+
+    #[test]
+    fn names_ignore_compression_extension() {
+        assert_eq!(entry_name(Path::new("dir/alpha.json.gz")), Some("alpha"));
+        assert_eq!(entry_name(Path::new("dir/alpha.json")), Some("alpha"));
+        assert_eq!(entry_name(Path::new("dir/alpha.zst")), Some("alpha"));
+    }
+}
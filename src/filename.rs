@@ -0,0 +1,310 @@
+use std::{
+    collections::HashSet,
+    io::{Error, ErrorKind, Result},
+    path::{Component, Path, PathBuf},
+};
+
+/// Characters that are unsafe or ambiguous in a filename on some filesystem
+const RESERVED: &[char] = &['%', '/', '\\', '<', '>', ':', '"', '|', '?', '*'];
+
+/// Percent-encodes a key so it can be used as a single filename.
+///
+/// Path separators, characters reserved on Windows, control characters and a leading `.`
+/// are encoded, so a key can't escape its directory or be hidden. Everything else,
+/// including non-ASCII text, is kept readable.
+pub fn encode(key: &str) -> String {
+    let mut name = String::with_capacity(key.len());
+    for (i, c) in key.chars().enumerate() {
+        if RESERVED.contains(&c) || c.is_ascii_control() || (i == 0 && c == '.') {
+            name.push_str(&format!("%{:02X}", c as u32));
+        } else {
+            name.push(c);
+        }
+    }
+    name
+}
+
+/// Reverses `encode`, keeping any `%` that isn't followed by two hex digits as it is
+pub fn decode(name: &str) -> String {
+    let bytes = name.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .filter(|_| bytes[i] == b'%')
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match hex {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// Turns a `/`-separated output name into a relative path, refusing any name that
+/// would resolve outside of the output root
+pub fn relative_path(name: &str) -> Result<PathBuf> {
+    let path = Path::new(name);
+    let safe = !name.is_empty()
+        && !name.contains(['\0', '\\'])
+        && name
+            .split('/')
+            .all(|segment| !matches!(segment, "" | "." | ".."))
+        && path
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)));
+    match safe {
+        true => Ok(path.to_path_buf()),
+        false => Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!("Refusing to write `{name}`, it is not a path inside the output directory"),
+        )),
+    }
+}
+
+/// What to do when an entry has the same name as one written earlier
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CollisionPolicy {
+    /// Fail before writing the entry
+    #[default]
+    Error,
+    /// Append `-1`, `-2`, etc. to the name
+    Suffix,
+    /// Keep the earlier entry and drop this one
+    Skip,
+    /// Replace the earlier entry
+    Overwrite,
+}
+
+impl std::str::FromStr for CollisionPolicy {
+    type Err = eyre::Report;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "error" => Ok(Self::Error),
+            "suffix" => Ok(Self::Suffix),
+            "skip" => Ok(Self::Skip),
+            "overwrite" => Ok(Self::Overwrite),
+            s => Err(eyre::eyre!(
+                "Unknown collision policy `{s}`, expected error, suffix, skip or overwrite"
+            )),
+        }
+    }
+}
+
+/// Whether names that only differ by case lead to the same file under `path`.
+///
+/// Looks up the nearest existing ancestor with a cased name under the opposite case, so
+/// nothing is written. Without such an ancestor, macOS and Windows are assumed to be
+/// case-insensitive and everything else case-sensitive.
+pub fn is_case_insensitive(path: &Path) -> bool {
+    let path = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+    for dir in path.ancestors().filter(|dir| dir.exists()) {
+        let (Some(parent), Some(name)) = (dir.parent(), dir.file_name().and_then(|n| n.to_str()))
+        else {
+            continue;
+        };
+        let swapped = name
+            .chars()
+            .map(|c| match c.is_lowercase() {
+                true => c.to_uppercase().collect::<String>(),
+                false => c.to_lowercase().collect(),
+            })
+            .collect::<String>();
+        if swapped != name {
+            return same_file(dir, &parent.join(swapped));
+        }
+    }
+    cfg!(any(target_os = "macos", target_os = "windows"))
+}
+
+#[cfg(unix)]
+fn same_file(a: &Path, b: &Path) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    match (a.metadata(), b.metadata()) {
+        (Ok(a), Ok(b)) => a.dev() == b.dev() && a.ino() == b.ino(),
+        _ => false,
+    }
+}
+
+#[cfg(not(unix))]
+fn same_file(_: &Path, b: &Path) -> bool {
+    b.exists()
+}
+
+/// Tracks the names written so far, to catch entries that would overwrite each other.
+///
+/// With `fold_case`, names are compared without case, as they are on a case-insensitive
+/// filesystem.
+#[derive(Clone, Debug, Default)]
+pub struct Claimed {
+    names: HashSet<String>,
+    pub policy: CollisionPolicy,
+    pub collisions: usize,
+    pub fold_case: bool,
+}
+
+impl Claimed {
+    /// The form of `name` that two colliding names share
+    fn fold(&self, name: &str) -> String {
+        match self.fold_case {
+            true => name.to_lowercase(),
+            false => name.to_string(),
+        }
+    }
+
+    /// Claims a name for every entry, returning the entries to write under their final names.
+    ///
+    /// With the `Error` policy nothing is claimed when any of the names collide.
+    pub fn claim_all<T>(&mut self, entries: Vec<(String, T)>) -> Result<Vec<(String, T)>> {
+        if self.policy == CollisionPolicy::Error {
+            let mut batch = HashSet::new();
+            for (name, _) in &entries {
+                let folded = self.fold(name);
+                if self.names.contains(&folded) || !batch.insert(folded) {
+                    return Err(Error::new(
+                        ErrorKind::AlreadyExists,
+                        format!("`{name}` collides with another entry of the same name"),
+                    ));
+                }
+            }
+            self.names.extend(batch);
+            return Ok(entries);
+        }
+
+        let mut claimed = Vec::with_capacity(entries.len());
+        for (name, value) in entries {
+            if self.names.insert(self.fold(&name)) {
+                claimed.push((name, value));
+                continue;
+            }
+            self.collisions += 1;
+            match self.policy {
+                CollisionPolicy::Suffix => {
+                    let name = (1..)
+                        .map(|i| format!("{name}-{i}"))
+                        .find(|suffixed| {
+                            let folded = self.fold(suffixed);
+                            self.names.insert(folded)
+                        })
+                        .expect("Ran out of suffixes");
+                    log::info!("Renaming colliding entry to `{name}`");
+                    claimed.push((name, value));
+                }
+                CollisionPolicy::Skip => log::info!("Skipping colliding entry `{name}`"),
+                _ => {
+                    log::info!("Overwriting colliding entry `{name}`");
+                    claimed.push((name, value));
+                }
+            }
+        }
+        Ok(claimed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+
+    #[test]
+    fn round_trips_unsafe_keys() {
+        for key in [
+            "a/b",
+            "..",
+            ".hidden",
+            "100%",
+            "C:\\temp",
+            "nul\0byte",
+            "café",
+        ] {
+            let name = encode(key);
+            assert!(!name.contains(['/', '\\', '\0']), "{name}");
+            assert!(!name.starts_with('.'), "{name}");
+            assert_eq!(decode(&name), key);
+        }
+        assert_eq!(encode("a/b"), "a%2Fb");
+        assert_eq!(decode("100%"), "100%");
+    }
+
+    #[test]
+    fn refuses_escaping_paths() {
+        assert!(relative_path("alpha/bravo").is_ok());
+        for name in [
+            "../alpha",
+            "alpha/../../bravo",
+            "/etc/passwd",
+            "",
+            "a//b",
+            "./a",
+            "a/./b",
+        ] {
+            assert!(relative_path(name).is_err(), "{name}");
+        }
+    }
+
+    fn names(names: &[&str]) -> Vec<(String, Value)> {
+        names
+            .iter()
+            .map(|name| (name.to_string(), Value::Null))
+            .collect()
+    }
+
+    fn claim(claimed: &mut Claimed, list: &[&str]) -> Result<Vec<String>> {
+        let entries = claimed.claim_all(names(list))?;
+        Ok(entries.into_iter().map(|(name, _)| name).collect())
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn detects_case_sensitive_filesystems() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(!is_case_insensitive(&dir.path().join("missing/output")));
+    }
+
+    #[test]
+    fn detects_case_collisions() {
+        let mut claimed = Claimed::default();
+        assert!(claim(&mut claimed, &["Alpha", "alpha"]).is_ok());
+        assert!(claim(&mut claimed, &["alpha"]).is_err());
+
+        let mut claimed = Claimed {
+            fold_case: true,
+            ..Default::default()
+        };
+        claim(&mut claimed, &["Alpha"]).unwrap();
+        assert!(claim(&mut claimed, &["bravo", "alpha"]).is_err());
+        // Nothing from the failed batch was claimed
+        assert!(claim(&mut claimed, &["bravo"]).is_ok());
+        assert!(claim(&mut claimed, &["x", "X"]).is_err());
+    }
+
+    #[test]
+    fn applies_collision_policy() -> Result<()> {
+        let mut claimed = Claimed {
+            policy: CollisionPolicy::Suffix,
+            fold_case: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            claim(&mut claimed, &["alpha", "alpha", "Alpha", "alpha-1"])?,
+            ["alpha", "alpha-1", "Alpha-2", "alpha-1-1"]
+        );
+        assert_eq!(claimed.collisions, 3);
+
+        claimed.policy = CollisionPolicy::Skip;
+        assert_eq!(claim(&mut claimed, &["alpha", "bravo"])?, ["bravo"]);
+        claimed.policy = CollisionPolicy::Overwrite;
+        assert_eq!(claim(&mut claimed, &["bravo"])?, ["bravo"]);
+        assert_eq!(claimed.collisions, 5);
+        Ok(())
+    }
+}
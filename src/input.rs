@@ -1,22 +1,80 @@
+mod archive;
 mod directory;
 mod file;
+mod filter;
+mod manifest;
 mod stdin;
 
+use crate::output::ArchiveFormat;
+pub use archive::InputArchive;
 pub use directory::InputDirectory;
 use eyre::{Report, Result, eyre};
 use file::InputFile;
+pub use filter::EntryFilter;
 use serde_json::Value;
-use std::{ops::Deref, path::PathBuf, sync::Arc};
+use std::{
+    ops::Deref,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
 use stdin::InputStdin;
 
 pub trait JsonSource: Send + Sync {
-    fn get_entries(&self, sort: bool) -> Vec<(String, Value)>;
     fn read_entry(&self) -> Result<(String, Value)>;
+    /// Reads entries one at a time, calling `f` with each as soon as it is read
+    fn visit_entries(
+        &self,
+        order: EntryOrder,
+        recursive: bool,
+        filter: &EntryFilter,
+        f: &mut SourceCallback,
+    ) -> Result<()>;
+    /// Reads entries one at a time like `visit_entries`, but with `recursive` naming files
+    /// in subdirectories by their relative path instead of nesting them as objects
+    fn visit_flat_entries(
+        &self,
+        order: EntryOrder,
+        _recursive: bool,
+        filter: &EntryFilter,
+        f: &mut SourceCallback,
+    ) -> Result<()> {
+        self.visit_entries(order, false, filter, f)
+    }
 }
 
 pub trait JsonReader: Send + Sync {
     fn get_object(&self) -> Result<Value>;
     fn read_line(&self, buf: &mut String) -> Result<()>;
+    /// Walks the top-level object, calling `f` with each key/value pair as soon as it is parsed
+    fn for_each_entry(&self, f: &mut EntryCallback) -> Result<()>;
+    /// Walks the object or array at the field path `at`, returning the surrounding envelope
+    fn for_each_entry_at(&self, at: &str, f: &mut EntryCallback) -> Result<Value>;
+    /// Name of the file being read, or `-` for stdin
+    fn source_name(&self) -> String;
+}
+
+/// Callback receiving each top-level entry of a streamed object
+pub type EntryCallback<'a> = dyn FnMut(String, Value) -> Result<()> + 'a;
+
+/// Callback receiving each entry of a directory or archive, along with the path it was read
+/// from relative to the input root, or `-` for stdin
+pub type SourceCallback<'a> = dyn FnMut(String, &Path, Value) -> Result<()> + 'a;
+
+/// Order to read the entries of a directory or archive in, unless a manifest lists them
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum EntryOrder {
+    /// Directory files sorted by name, archive members in the order they were added
+    #[default]
+    Default,
+    /// Sorted by name, comparing bytes
+    Sorted,
+    /// Sorted by name, with numbered names like array indices ordered by value
+    Natural,
+}
+
+/// Names an array element by its zero-padded index
+pub fn index_key(i: usize) -> String {
+    format!("{i:06}")
 }
 
 #[derive(Clone)]
@@ -32,6 +90,8 @@ impl std::str::FromStr for JsonSourceInput {
                 let path = PathBuf::from(input);
                 if path.is_dir() {
                     Ok(JsonSourceInput(Arc::new(InputDirectory::new(path))))
+                } else if let Some((format, _)) = ArchiveFormat::from_path(&path) {
+                    Ok(JsonSourceInput(Arc::new(InputArchive::new(path, format))))
                 } else {
                     Err(eyre!("Cannot read entries from file: {input}"))
                 }
@@ -0,0 +1,332 @@
+use super::{
+    EntryFilter, EntryOrder, JsonSource, SourceCallback, directory::path_components,
+    file::open_file, manifest::visit_manifest,
+};
+use crate::{
+    compression::decompress,
+    manifest::{MANIFEST_FILE, Manifest},
+    output::ArchiveFormat,
+    processor::key_order::natural_cmp,
+};
+use eyre::{Result, eyre};
+use serde_json::{Map, Value};
+use std::{
+    cmp::Ordering,
+    collections::HashMap,
+    fs::File,
+    io::Read,
+    path::{Component, Path, PathBuf},
+};
+
+/// Reads the members of a tar or zip archive as if they were files in a directory.
+///
+/// Members are named by their path inside the archive without the extension,
+/// so `build/alpha.json` is read as the entry `build/alpha`.
+#[derive(Clone)]
+pub struct InputArchive {
+    path: PathBuf,
+    format: ArchiveFormat,
+}
+
+impl InputArchive {
+    pub fn new(path: PathBuf, format: ArchiveFormat) -> Self {
+        InputArchive { path, format }
+    }
+
+    /// Calls `f` with the path and raw content of every regular file in the archive
+    fn visit_members(&self, f: &mut dyn FnMut(PathBuf, Vec<u8>) -> Result<()>) -> Result<()> {
+        log::info!("Reading archive {}", self.path.display());
+        match self.format {
+            ArchiveFormat::Tar => {
+                let mut archive = tar::Archive::new(open_file(&self.path)?);
+                for entry in archive.entries()? {
+                    let mut entry = entry?;
+                    if !entry.header().entry_type().is_file() {
+                        continue;
+                    }
+                    let path = normalize(&entry.path()?);
+                    let mut content = Vec::new();
+                    entry.read_to_end(&mut content)?;
+                    f(path, content)?;
+                }
+            }
+            ArchiveFormat::Zip => {
+                let mut archive = zip::ZipArchive::new(File::open(&self.path)?)?;
+                for i in 0..archive.len() {
+                    let mut member = archive.by_index(i)?;
+                    if !member.is_file() {
+                        continue;
+                    }
+                    let path = member
+                        .enclosed_name()
+                        .ok_or_else(|| eyre!("Unsafe zip member path `{}`", member.name()))?;
+                    let mut content = Vec::new();
+                    member.read_to_end(&mut content)?;
+                    f(path, content)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads the manifest member, if the archive has one
+    fn read_manifest(&self) -> Result<Option<Manifest>> {
+        match self.format {
+            ArchiveFormat::Tar => {
+                let mut archive = tar::Archive::new(open_file(&self.path)?);
+                for entry in archive.entries()? {
+                    let entry = entry?;
+                    if normalize(&entry.path()?) == Path::new(MANIFEST_FILE) {
+                        return Ok(Some(Manifest::read(entry)?));
+                    }
+                }
+                Ok(None)
+            }
+            ArchiveFormat::Zip => {
+                let mut archive = zip::ZipArchive::new(File::open(&self.path)?)?;
+                match archive.by_name(MANIFEST_FILE) {
+                    Ok(member) => Ok(Some(Manifest::read(member)?)),
+                    Err(zip::result::ZipError::FileNotFound) => Ok(None),
+                    Err(e) => Err(e.into()),
+                }
+            }
+        }
+    }
+
+    /// Visits each member that passes `filter` as its path, path components and parsed value
+    fn visit_values(
+        &self,
+        filter: &EntryFilter,
+        f: &mut dyn FnMut(PathBuf, Vec<String>, Value) -> Result<()>,
+    ) -> Result<()> {
+        let mut skipped = 0;
+        let mut skip = |path: &Path, reason: &dyn std::fmt::Display| {
+            log::warn!("Skipping {} ({reason})", path.display());
+            skipped += 1;
+        };
+        self.visit_members(&mut |path, content| {
+            if let Some(reason) = filter.skip_reason(&path, false) {
+                log::info!("Skipping {} ({reason})", path.display());
+                return Ok(());
+            }
+            let Some(components) = path_components(&path) else {
+                if filter.is_strict() {
+                    return Err(eyre!(
+                        "Cannot name an entry after archive member {}, which isn't valid UTF-8",
+                        path.display()
+                    ));
+                }
+                skip(&path, &"name isn't valid UTF-8");
+                return Ok(());
+            };
+            let value = match read_member(&path, &content) {
+                Ok(value) => value,
+                Err(e) if !filter.is_strict() => {
+                    skip(&path, &e);
+                    return Ok(());
+                }
+                Err(e) => return Err(e.wrap_err(format!("Error reading {}", path.display()))),
+            };
+            log::info!("Appending entry {} from {:?}", components.join("/"), path);
+            f(path, components, value)
+        })?;
+        if skipped > 0 {
+            log::warn!("Skipped {skipped} unreadable archive members");
+        }
+        Ok(())
+    }
+}
+
+impl JsonSource for InputArchive {
+    fn read_entry(&self) -> Result<(String, Value)> {
+        Err(eyre!(
+            "Cannot read a single entry from archive {}",
+            self.path.display()
+        ))
+    }
+
+    /// Streams members in archive order, unless they have to be sorted or nested first.
+    ///
+    /// Archives with a manifest are read in full, then in the order it lists.
+    fn visit_entries(
+        &self,
+        order: EntryOrder,
+        recursive: bool,
+        filter: &EntryFilter,
+        f: &mut SourceCallback,
+    ) -> Result<()> {
+        if let Some(manifest) = self.read_manifest()? {
+            log::info!("Reading entries in the order of {MANIFEST_FILE}");
+            let mut members = HashMap::new();
+            self.visit_members(&mut |path, content| {
+                members.insert(path, content);
+                Ok(())
+            })?;
+            let mut read = |path: &Path| -> Result<Vec<u8>> {
+                let content = members
+                    .remove(path)
+                    .ok_or_else(|| eyre!("Archive has no member {}", path.display()))?;
+                let mut decompressed = Vec::new();
+                decompress(content.as_slice(), Some(path))?.read_to_end(&mut decompressed)?;
+                Ok(decompressed)
+            };
+            return visit_manifest(manifest, recursive, filter, &mut read, f);
+        }
+
+        if order == EntryOrder::Default && !recursive {
+            return self.visit_values(filter, &mut |path, components, value| {
+                f(components.join("/"), &path, value)
+            });
+        }
+
+        let mut members = Vec::new();
+        self.visit_values(filter, &mut |path, components, value| {
+            members.push((components, path, value));
+            Ok(())
+        })?;
+        match order {
+            EntryOrder::Default => {}
+            EntryOrder::Sorted => members.sort_by(|(a, _, _), (b, _, _)| a.cmp(b)),
+            EntryOrder::Natural => members.sort_by(|(a, _, _), (b, _, _)| compare_paths(a, b)),
+        }
+        if !recursive {
+            return members
+                .into_iter()
+                .try_for_each(|(components, path, value)| f(components.join("/"), &path, value));
+        }
+
+        let mut nested = Nested::default();
+        for (components, path, value) in members {
+            nested.insert(&path, &components, value)?;
+        }
+        nested.visit(f)
+    }
+}
+
+fn read_member(path: &Path, content: &[u8]) -> Result<Value> {
+    Ok(serde_json::from_reader(decompress(content, Some(path))?)?)
+}
+
+/// Drops the `./` prefix some tar tools add to member paths
+fn normalize(path: &Path) -> PathBuf {
+    path.components()
+        .filter(|component| matches!(component, Component::Normal(_)))
+        .collect()
+}
+
+fn compare_paths(a: &[String], b: &[String]) -> Ordering {
+    a.iter()
+        .zip(b)
+        .map(|(a, b)| natural_cmp(a, b))
+        .find(|ordering| ordering.is_ne())
+        .unwrap_or_else(|| a.len().cmp(&b.len()))
+}
+
+/// Entries nested as objects by directory, along with the file or directory under the input
+/// root that each top-level entry comes from
+#[derive(Default)]
+pub(super) struct Nested {
+    root: Map<String, Value>,
+    paths: HashMap<String, PathBuf>,
+}
+
+impl Nested {
+    pub(super) fn insert(
+        &mut self,
+        path: &Path,
+        components: &[String],
+        value: Value,
+    ) -> Result<()> {
+        if let Some(name) = components.first() {
+            self.paths
+                .entry(name.clone())
+                .or_insert_with(|| path.components().take(1).collect());
+        }
+        nest(&mut self.root, components, value)
+            .map_err(|e| e.wrap_err(format!("Error reading {}", path.display())))
+    }
+
+    /// Calls `f` with each top-level entry
+    pub(super) fn visit(self, f: &mut SourceCallback) -> Result<()> {
+        let mut paths = self.paths;
+        self.root.into_iter().try_for_each(|(name, value)| {
+            let path = paths.remove(&name).unwrap_or_default();
+            f(name, &path, value)
+        })
+    }
+}
+
+/// Inserts `value` into `map` under nested objects named by `components`, failing when
+/// an entry already holds its place
+pub(super) fn nest(
+    map: &mut Map<String, Value>,
+    components: &[String],
+    value: Value,
+) -> Result<()> {
+    match components {
+        [name] => match map.contains_key(name) {
+            true => Err(eyre!(
+                "Entry `{name}` was already read from another file with the same name"
+            )),
+            false => {
+                map.insert(name.clone(), value);
+                Ok(())
+            }
+        },
+        [dir, rest @ ..] => match map
+            .entry(dir.clone())
+            .or_insert_with(|| Value::Object(Map::new()))
+        {
+            Value::Object(child) => nest(child, rest, value),
+            _ => Err(eyre!(
+                "Archive entry `{dir}` is both a file and a directory"
+            )),
+        },
+        [] => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn names_members_by_path() {
+        assert_eq!(
+            path_components(Path::new("./build/alpha.json.gz")),
+            Some(vec!["build".to_string(), "alpha".to_string()])
+        );
+    }
+
+    #[test]
+    fn orders_mixed_names_naturally() {
+        let mut paths = [
+            "b", "a10", "10", "a2", "2", "a", "1", "a1", "01", "b/2", "b/10",
+        ]
+        .map(|path| path_components(Path::new(&format!("{path}.json"))).unwrap());
+        paths.sort_by(|a, b| compare_paths(a, b));
+        assert_eq!(
+            paths.map(|path| path.join("/")),
+            [
+                "1", "01", "2", "10", "a", "a1", "a2", "a10", "b", "b/2", "b/10"
+            ]
+        );
+    }
+
+    #[test]
+    fn nests_members_by_directory() {
+        let mut root = Map::new();
+        let path = |path: &str| path_components(Path::new(path)).unwrap();
+        nest(&mut root, &path("letters/alpha.json"), json!(1)).unwrap();
+        nest(&mut root, &path("letters/bravo.json"), json!(2)).unwrap();
+        nest(&mut root, &path("charlie.json"), json!(3)).unwrap();
+        assert!(nest(&mut root, &path("charlie/delta.json"), json!(4)).is_err());
+        assert!(nest(&mut root, &path("letters/alpha.json.gz"), json!(5)).is_err());
+        assert!(nest(&mut root, &path("letters.json"), json!(6)).is_err());
+        assert_eq!(
+            Value::Object(root),
+            json!({"letters": {"alpha": 1, "bravo": 2}, "charlie": 3})
+        );
+    }
+}
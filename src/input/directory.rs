@@ -1,7 +1,22 @@
-use super::{file::read_object, JsonSource};
-use eyre::Result;
-use serde_json::Value;
-use std::path::PathBuf;
+use super::{
+    EntryFilter, EntryOrder, JsonSource, SourceCallback,
+    file::{open_file, read_object},
+    manifest::visit_manifest,
+};
+use crate::{
+    compression::entry_name,
+    filename::decode,
+    manifest::{MANIFEST_FILE, Manifest},
+    processor::key_order::natural_cmp,
+};
+use eyre::{Result, eyre};
+use serde_json::{Map, Value};
+use std::{
+    ffi::OsStr,
+    fs::File,
+    io::Read,
+    path::{Component, Path, PathBuf},
+};
 
 #[derive(Clone)]
 pub struct InputDirectory {
@@ -12,19 +27,73 @@ impl InputDirectory {
     pub fn new(path: PathBuf) -> Self {
         InputDirectory { path }
     }
-}
 
-impl JsonSource for InputDirectory {
-    fn get_entries(&self, sort: bool) -> Vec<(String, Value)> {
-        read_entries(&self.path, sort).expect("Error reading entries from directory")
+    /// Reads the entries listed in the directory's manifest, returning `false` when it has none
+    fn visit_manifest(
+        &self,
+        recursive: bool,
+        filter: &EntryFilter,
+        f: &mut SourceCallback,
+    ) -> Result<bool> {
+        let path = self.path.join(MANIFEST_FILE);
+        if !path.is_file() {
+            return Ok(false);
+        }
+        log::info!("Reading entries in the order of {}", path.display());
+        let manifest = Manifest::read(File::open(&path)?)?;
+        let mut read = |relative: &Path| -> Result<Vec<u8>> {
+            let mut content = Vec::new();
+            open_file(&self.path.join(relative))?.read_to_end(&mut content)?;
+            Ok(content)
+        };
+        visit_manifest(manifest, recursive, filter, &mut read, f)?;
+        Ok(true)
     }
+}
 
+impl JsonSource for InputDirectory {
     fn read_entry(&self) -> Result<(String, Value)> {
         let file = &self.path;
         log::info!("Reading file {}", &file.display());
         let object = read_object(file)?;
-        let name = file.file_stem().unwrap().to_str().unwrap();
-        Ok((name.to_string(), object))
+        let name = entry_name(file)
+            .ok_or_else(|| eyre!("Cannot name an entry after {}", file.display()))?;
+        Ok((decode(name), object))
+    }
+
+    /// Reads files in sorted filename order, since directory order differs between machines
+    fn visit_entries(
+        &self,
+        order: EntryOrder,
+        recursive: bool,
+        filter: &EntryFilter,
+        f: &mut SourceCallback,
+    ) -> Result<()> {
+        let filter = filter.clone().ignore_file(&self.path)?;
+        if self.visit_manifest(recursive, &filter, f)? {
+            return Ok(());
+        }
+        let mut walker = DirectoryWalker::new(&self.path, order, recursive, &filter);
+        walker.visit(&self.path, f)?;
+        walker.report_skipped();
+        Ok(())
+    }
+
+    fn visit_flat_entries(
+        &self,
+        order: EntryOrder,
+        recursive: bool,
+        filter: &EntryFilter,
+        f: &mut SourceCallback,
+    ) -> Result<()> {
+        let filter = filter.clone().ignore_file(&self.path)?;
+        if self.visit_manifest(false, &filter, f)? {
+            return Ok(());
+        }
+        let mut walker = DirectoryWalker::new(&self.path, order, recursive, &filter).flatten(true);
+        walker.visit(&self.path, f)?;
+        walker.report_skipped();
+        Ok(())
     }
 }
 
@@ -42,23 +111,189 @@ impl std::str::FromStr for InputDirectory {
     }
 }
 
-pub fn read_entries(dir: &PathBuf, sort: bool) -> Result<Vec<(String, Value)>> {
-    let mut entries: Vec<(String, Value)> = Vec::new();
-    let dir_path = PathBuf::from(dir);
-    let dir_entries = std::fs::read_dir(dir_path)?;
-    for entry in dir_entries {
-        if let Ok(entry) = entry {
-            let file_path = entry.path();
-            let object = read_object(&file_path)?;
-            let name = file_path.file_stem().unwrap().to_str().unwrap();
+/// Reports a second file named like an earlier entry, by stem or by extension
+fn duplicate_entry(name: &str, path: &Path) -> eyre::Report {
+    eyre!(
+        "Entry `{name}` from {} was already read from another file with the same name",
+        path.display()
+    )
+}
+
+/// Splits a path relative to the input root into decoded directory names followed by the entry name
+pub(super) fn path_components(path: &Path) -> Option<Vec<String>> {
+    let mut components = path
+        .parent()?
+        .components()
+        .filter_map(|component| match component {
+            Component::Normal(name) => Some(name.to_str().map(decode)),
+            _ => None,
+        })
+        .collect::<Option<Vec<_>>>()?;
+    components.push(decode(entry_name(path)?));
+    Some(components)
+}
+
+/// Walks an input directory in sorted filename order, reading each file in turn so only one entry
+/// is held in memory at a time.
+///
+/// Paths are matched against `filter` relative to `root`. Unless the filter is strict,
+/// subdirectories and files that can't be read as JSON or named are skipped instead of failing.
+struct DirectoryWalker<'a> {
+    root: &'a Path,
+    /// Compare numbered names by value instead of byte by byte
+    natural: bool,
+    recursive: bool,
+    /// Name files in subdirectories by their relative path instead of nesting them as objects
+    flatten: bool,
+    filter: &'a EntryFilter,
+    /// Canonical paths of the directories being walked, to catch symlink loops
+    ancestors: Vec<PathBuf>,
+    /// Number of files skipped because they couldn't be read or named
+    skipped: usize,
+}
+
+impl<'a> DirectoryWalker<'a> {
+    fn new(root: &'a Path, order: EntryOrder, recursive: bool, filter: &'a EntryFilter) -> Self {
+        Self {
+            root,
+            natural: order == EntryOrder::Natural,
+            recursive,
+            flatten: false,
+            filter,
+            ancestors: Vec::new(),
+            skipped: 0,
+        }
+    }
+
+    fn flatten(mut self, flatten: bool) -> Self {
+        self.flatten = flatten;
+        self
+    }
+
+    /// Reads every entry in `dir` along with its path relative to the root, with `recursive`
+    /// rebuilding subdirectories as nested objects
+    fn read(&mut self, dir: &Path) -> Result<Vec<(String, PathBuf, Value)>> {
+        let mut entries = Vec::new();
+        self.visit(dir, &mut |name, path, object| {
+            entries.push((name, path.to_path_buf(), object));
+            Ok(())
+        })?;
+        Ok(entries)
+    }
+
+    fn visit(&mut self, dir: &Path, f: &mut SourceCallback) -> Result<()> {
+        self.ancestors.push(dir.canonicalize()?);
+        let mut file_paths: Vec<PathBuf> = std::fs::read_dir(dir)?
+            .flatten()
+            .map(|entry| entry.path())
+            .collect();
+
+        // Paths are sorted before any file is read, so the output doesn't depend on directory
+        // order. Ties between names are broken by the full filename
+        file_paths.sort_by(|a, b| {
+            let (a_name, b_name) = (
+                entry_name(a).unwrap_or_default(),
+                entry_name(b).unwrap_or_default(),
+            );
+            match self.natural {
+                true => natural_cmp(a_name, b_name),
+                false => a_name.cmp(b_name),
+            }
+            .then_with(|| a.cmp(b))
+        });
+
+        for file_path in file_paths {
+            let relative = file_path.strip_prefix(self.root).unwrap_or(&file_path);
+            let is_dir = file_path.is_dir();
+            if let Some(reason) = self.skip_reason(&file_path, relative, is_dir) {
+                log::info!("Skipping {} ({reason})", file_path.display());
+                continue;
+            }
+            if is_dir && self.recursive && self.flatten {
+                self.visit(&file_path, f)?;
+                continue;
+            }
+            if is_dir && self.recursive {
+                let Some(name) = file_path.file_name().and_then(OsStr::to_str) else {
+                    self.skip_unnamed(&file_path)?;
+                    continue;
+                };
+                log::info!("Nesting entry {} from {:?}", name, file_path);
+                let mut object = Map::new();
+                for (name, path, value) in self.read(&file_path)? {
+                    if object.insert(name.clone(), value).is_some() {
+                        return Err(duplicate_entry(&name, &path));
+                    }
+                }
+                f(decode(name), relative, Value::Object(object))?;
+                continue;
+            }
+            if is_dir && !self.filter.is_strict() {
+                log::info!("Skipping {} (directory)", file_path.display());
+                continue;
+            }
+            let name = match self.flatten {
+                true => path_components(relative).map(|components| components.join("/")),
+                false => entry_name(&file_path).map(decode),
+            };
+            let Some(name) = name else {
+                self.skip_unnamed(&file_path)?;
+                continue;
+            };
+            let object = match read_object(&file_path) {
+                Ok(object) => object,
+                Err(e) if !self.filter.is_strict() => {
+                    self.skip(&file_path, e);
+                    continue;
+                }
+                Err(e) => return Err(e.wrap_err(format!("Error reading {}", file_path.display()))),
+            };
             log::info!("Appending entry {} from {:?}", name, file_path);
-            entries.push((name.to_string(), object));
+            f(name, relative, object)?;
         }
+        self.ancestors.pop();
+        Ok(())
     }
 
-    if sort {
-        entries.sort_by(|a, b| a.0.cmp(&b.0));
+    fn skip(&mut self, path: &Path, reason: impl std::fmt::Display) {
+        log::warn!("Skipping {} ({reason})", path.display());
+        self.skipped += 1;
     }
 
-    Ok(entries)
+    /// Skips a file whose name isn't valid UTF-8, or fails when the filter is strict
+    fn skip_unnamed(&mut self, path: &Path) -> Result<()> {
+        match self.filter.is_strict() {
+            true => Err(eyre!(
+                "Cannot name an entry after {}, which isn't valid UTF-8",
+                path.display()
+            )),
+            false => {
+                self.skip(path, "name isn't valid UTF-8");
+                Ok(())
+            }
+        }
+    }
+
+    /// Warns how many files were skipped, once the walk is done
+    fn report_skipped(&self) {
+        if self.skipped > 0 {
+            log::warn!("Skipped {} unreadable files", self.skipped);
+        }
+    }
+
+    fn skip_reason(&self, path: &Path, relative: &Path, is_dir: bool) -> Option<&'static str> {
+        if path.is_symlink() {
+            if !self.filter.follows_symlinks() {
+                return Some("symlink");
+            }
+            let loops = is_dir
+                && path
+                    .canonicalize()
+                    .is_ok_and(|target| self.ancestors.contains(&target));
+            if loops {
+                return Some("symlink loop");
+            }
+        }
+        self.filter.skip_reason(relative, is_dir)
+    }
 }
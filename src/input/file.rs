@@ -1,23 +1,24 @@
-use super::JsonReader;
+use super::{EntryCallback, JsonReader, index_key};
+use crate::{compression::decompress, processor::FieldPath};
 use eyre::{Result, eyre};
-use serde_json::Value;
+use serde::de::{self, DeserializeSeed, Deserializer, MapAccess, SeqAccess, Visitor};
+use serde_json::{Map, Value};
 use std::{
     fs::File,
-    io::{BufRead, BufReader},
-    path::PathBuf,
+    io::{BufRead, BufReader, Read},
+    path::{Path, PathBuf},
     sync::{Arc, Mutex},
 };
 
 #[derive(Clone)]
 pub struct InputFile {
     path: PathBuf,
-    reader: Arc<Mutex<BufReader<File>>>,
+    reader: Arc<Mutex<Box<dyn BufRead + Send>>>,
 }
 
 impl InputFile {
     pub fn new(path: PathBuf) -> Result<Self> {
-        let file = File::open(&path)?;
-        let reader = BufReader::new(file);
+        let reader = open_file(&path)?;
         Ok(Self {
             path,
             reader: Arc::new(Mutex::new(reader)),
@@ -35,12 +36,156 @@ impl JsonReader for InputFile {
         reader.read_line(buf)?;
         Ok(())
     }
+
+    fn for_each_entry(&self, f: &mut EntryCallback) -> Result<()> {
+        stream_entries(open_file(&self.path)?, f)
+    }
+
+    fn for_each_entry_at(&self, at: &str, f: &mut EntryCallback) -> Result<Value> {
+        stream_entries_at(open_file(&self.path)?, at, f)
+    }
+
+    fn source_name(&self) -> String {
+        self.path.file_name().map_or_else(
+            || self.path.display().to_string(),
+            |name| name.to_string_lossy().into_owned(),
+        )
+    }
+}
+
+/// Opens a file for buffered reading, decompressing it on the fly when needed
+pub fn open_file(path: &Path) -> Result<Box<dyn BufRead + Send>> {
+    let file = File::open(path)?;
+    Ok(decompress(BufReader::new(file), Some(path))?)
+}
+
+/// Parses a single top-level JSON object or array from `reader` one entry at a time.
+///
+/// Only the current key/value pair is held in memory, so the peak memory use is
+/// bounded by the largest single value rather than the whole document. Array elements
+/// are keyed by their zero-padded index.
+pub fn stream_entries<R: Read>(reader: R, f: &mut EntryCallback) -> Result<()> {
+    stream_path(reader, &[], f).map(|_| ())
+}
+
+/// Streams the entries of the object or array found at the field path `at`.
+///
+/// Everything outside of the selected value is collected into an envelope, which is
+/// returned with the selected value replaced by `null`.
+pub fn stream_entries_at<R: Read>(reader: R, at: &str, f: &mut EntryCallback) -> Result<Value> {
+    let path = at.parse::<FieldPath>()?.tokens()?;
+    stream_path(reader, &path, f)
+}
+
+fn stream_path<R: Read>(reader: R, path: &[String], f: &mut EntryCallback) -> Result<Value> {
+    // serde can only carry a message, so keep the callback's own error to return instead of
+    // one ending in the parser's line and column
+    let mut failure = None;
+    let mut callback = |key: String, value: Value| {
+        f(key, value).map_err(|e| {
+            let message = e.to_string();
+            failure = Some(e);
+            eyre!(message)
+        })
+    };
+    let mut deserializer = serde_json::Deserializer::from_reader(reader);
+    let envelope = PathSeed {
+        path,
+        callback: &mut callback,
+    }
+    .deserialize(&mut deserializer);
+    if let Some(e) = failure {
+        return Err(e);
+    }
+    let envelope = envelope?;
+    deserializer.end()?;
+    Ok(envelope)
+}
+
+/// Descends along `path`, then hands each entry of the value found there to `callback`
+struct PathSeed<'a, 'b> {
+    path: &'a [String],
+    callback: &'a mut EntryCallback<'b>,
+}
+
+impl<'de> DeserializeSeed<'de> for PathSeed<'_, '_> {
+    type Value = Value;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Value, D::Error> {
+        deserializer.deserialize_any(self)
+    }
+}
+
+impl<'de> Visitor<'de> for PathSeed<'_, '_> {
+    type Value = Value;
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self.path.first() {
+            Some(key) => write!(formatter, "a JSON object or array containing `{key}`"),
+            None => formatter.write_str("a JSON object or array"),
+        }
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Value, A::Error> {
+        let Some((head, tail)) = self.path.split_first() else {
+            while let Some(key) = map.next_key::<String>()? {
+                let value: Value = map.next_value()?;
+                (self.callback)(key, value).map_err(de::Error::custom)?;
+            }
+            return Ok(Value::Null);
+        };
+        let mut envelope = Map::new();
+        let mut found = false;
+        while let Some(key) = map.next_key::<String>()? {
+            let value = match key == *head {
+                true => {
+                    found = true;
+                    map.next_value_seed(PathSeed {
+                        path: tail,
+                        callback: &mut *self.callback,
+                    })?
+                }
+                false => map.next_value()?,
+            };
+            envelope.insert(key, value);
+        }
+        match found {
+            true => Ok(Value::Object(envelope)),
+            false => Err(de::Error::custom(format!("Key `{head}` not found"))),
+        }
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Value, A::Error> {
+        let Some((head, tail)) = self.path.split_first() else {
+            let mut i: usize = 0;
+            while let Some(value) = seq.next_element::<Value>()? {
+                (self.callback)(index_key(i), value).map_err(de::Error::custom)?;
+                i += 1;
+            }
+            return Ok(Value::Null);
+        };
+        let not_found = || de::Error::custom(format!("Index `{head}` not found"));
+        let index = head.parse::<usize>().map_err(|_| not_found())?;
+        let mut envelope = Vec::new();
+        while envelope.len() < index {
+            let value = seq.next_element::<Value>()?.ok_or_else(not_found)?;
+            envelope.push(value);
+        }
+        let seed = PathSeed {
+            path: tail,
+            callback: &mut *self.callback,
+        };
+        envelope.push(seq.next_element_seed(seed)?.ok_or_else(not_found)?);
+        while let Some(value) = seq.next_element::<Value>()? {
+            envelope.push(value);
+        }
+        Ok(Value::Array(envelope))
+    }
 }
 
 pub fn read_object(input: &PathBuf) -> Result<Value> {
     if input.is_file() {
-        let file = File::open(input)?;
-        let reader = BufReader::new(file);
+        let reader = open_file(input)?;
         let json_value = serde_json::from_reader(reader)?;
         Ok(json_value)
     } else if input.is_dir() {
@@ -48,7 +193,8 @@ pub fn read_object(input: &PathBuf) -> Result<Value> {
         for entry in std::fs::read_dir(input)? {
             let path = entry?.path();
             if path.is_file() {
-                let content = std::fs::read_to_string(&path)?;
+                let mut content = String::new();
+                open_file(&path)?.read_to_string(&mut content)?;
                 let filename = path
                     .file_name()
                     .ok_or_else(|| eyre!("Invalid filename"))?
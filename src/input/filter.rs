@@ -0,0 +1,168 @@
+use crate::manifest::MANIFEST_FILE;
+use eyre::{Result, eyre};
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use std::path::{Component, Path};
+
+/// Name of the gitignore-style file read from the root of an input directory
+pub const IGNORE_FILE: &str = ".jsrmxignore";
+
+/// Decides which files of an input directory or archive are read as entries.
+///
+/// Globs are matched against the path relative to the input root, so `*.json`
+/// matches files in subdirectories too.
+#[derive(Clone, Debug, Default)]
+pub struct EntryFilter {
+    include: Option<GlobSet>,
+    exclude: Option<GlobSet>,
+    ignore: Option<Gitignore>,
+    hidden: bool,
+    strict: bool,
+    no_follow_symlinks: bool,
+}
+
+impl EntryFilter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only read files matching one of these globs
+    pub fn include(mut self, patterns: Option<Vec<String>>) -> Result<Self> {
+        self.include = patterns.map(glob_set).transpose()?;
+        Ok(self)
+    }
+
+    /// Skip files and directories matching any of these globs
+    pub fn exclude(mut self, patterns: Option<Vec<String>>) -> Result<Self> {
+        self.exclude = patterns.map(glob_set).transpose()?;
+        Ok(self)
+    }
+
+    /// Read hidden files and directories, which are skipped by default
+    pub fn hidden(mut self, hidden: bool) -> Self {
+        self.hidden = hidden;
+        self
+    }
+
+    /// Fail on the first unreadable entry instead of skipping it
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Follow symlinked files and directories, which is the default
+    pub fn follow_symlinks(mut self, follow: bool) -> Self {
+        self.no_follow_symlinks = !follow;
+        self
+    }
+
+    pub fn follows_symlinks(&self) -> bool {
+        !self.no_follow_symlinks
+    }
+
+    pub fn is_strict(&self) -> bool {
+        self.strict
+    }
+
+    /// Loads the `.jsrmxignore` file from `root`, when there is one
+    pub fn ignore_file(mut self, root: &Path) -> Result<Self> {
+        let path = root.join(IGNORE_FILE);
+        if !path.is_file() {
+            return Ok(self);
+        }
+        log::info!("Reading ignore patterns from {}", path.display());
+        let mut builder = GitignoreBuilder::new(root);
+        if let Some(e) = builder.add(&path) {
+            return Err(eyre!("Error reading {}: {e}", path.display()));
+        }
+        self.ignore = Some(builder.build()?);
+        Ok(self)
+    }
+
+    /// Returns why the file or directory at `relative` should be skipped, if it should be
+    pub fn skip_reason(&self, relative: &Path, is_dir: bool) -> Option<&'static str> {
+        let hidden = relative.components().any(|component| match component {
+            Component::Normal(name) => name.to_string_lossy().starts_with('.'),
+            _ => false,
+        });
+        if relative.file_name().is_some_and(|name| name == IGNORE_FILE) {
+            Some("ignore file")
+        } else if relative == Path::new(MANIFEST_FILE) {
+            Some("manifest")
+        } else if hidden && !self.hidden {
+            Some("hidden")
+        } else if self
+            .exclude
+            .as_ref()
+            .is_some_and(|set| set.is_match(relative))
+        {
+            Some("excluded")
+        } else if self
+            .ignore
+            .as_ref()
+            .is_some_and(|ignore| ignore.matched(relative, is_dir).is_ignore())
+        {
+            Some("listed in .jsrmxignore")
+        } else if !is_dir
+            && self
+                .include
+                .as_ref()
+                .is_some_and(|set| !set.is_match(relative))
+        {
+            Some("not included")
+        } else {
+            None
+        }
+    }
+}
+
+fn glob_set(patterns: Vec<String>) -> Result<GlobSet> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        builder.add(Glob::new(&pattern)?);
+    }
+    Ok(builder.build()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn skips_hidden_unless_asked() {
+        let filter = EntryFilter::new();
+        assert_eq!(
+            filter.skip_reason(Path::new(".DS_Store"), false),
+            Some("hidden")
+        );
+        assert_eq!(filter.skip_reason(Path::new("alpha.json"), false), None);
+        let filter = filter.hidden(true);
+        assert_eq!(filter.skip_reason(Path::new(".DS_Store"), false), None);
+        assert_eq!(
+            filter.skip_reason(Path::new(IGNORE_FILE), false),
+            Some("ignore file")
+        );
+        assert_eq!(
+            filter.skip_reason(Path::new(MANIFEST_FILE), false),
+            Some("manifest")
+        );
+    }
+
+    #[test]
+    fn include_applies_to_files_only() -> Result<()> {
+        let filter = EntryFilter::new()
+            .include(Some(vec!["*.json".to_string()]))?
+            .exclude(Some(vec!["*.draft.json".to_string()]))?;
+        assert_eq!(filter.skip_reason(Path::new("sub/alpha.json"), false), None);
+        assert_eq!(filter.skip_reason(Path::new("sub"), true), None);
+        assert_eq!(
+            filter.skip_reason(Path::new("README.md"), false),
+            Some("not included")
+        );
+        assert_eq!(
+            filter.skip_reason(Path::new("bravo.draft.json"), false),
+            Some("excluded")
+        );
+        Ok(())
+    }
+}
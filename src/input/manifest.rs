@@ -0,0 +1,46 @@
+use super::{EntryFilter, SourceCallback, archive::Nested};
+use crate::{
+    filename::relative_path,
+    manifest::{MANIFEST_FILE, Manifest},
+};
+use eyre::{Result, eyre};
+use serde_json::Value;
+use std::path::Path;
+
+/// Reads the entries listed in `manifest` in their original order, verifying each checksum.
+///
+/// `read` returns the uncompressed content of a file by its path relative to the input root.
+/// Every listed file is read, including those in subdirectories, and named by the keys the
+/// manifest records for it rather than its path, which `recursive` nests as objects.
+pub(super) fn visit_manifest(
+    manifest: Manifest,
+    recursive: bool,
+    filter: &EntryFilter,
+    read: &mut dyn FnMut(&Path) -> Result<Vec<u8>>,
+    f: &mut SourceCallback,
+) -> Result<()> {
+    let mut nested = Nested::default();
+    for entry in manifest.ordered() {
+        let path = relative_path(&entry.path)?;
+        if let Some(reason) = filter.skip_reason(&path, false) {
+            log::info!("Skipping {} ({reason})", path.display());
+            continue;
+        }
+        let content = read(&path).map_err(|e| {
+            e.wrap_err(format!(
+                "{} is listed in {MANIFEST_FILE} but can't be read",
+                entry.path
+            ))
+        })?;
+        entry.verify(&content)?;
+        let value: Value = serde_json::from_slice(&content)
+            .map_err(|e| eyre!("Error reading {}: {e}", entry.path))?;
+        let components = entry.keys();
+        log::info!("Appending entry {} from {:?}", components.join("/"), path);
+        match recursive {
+            true => nested.insert(&path, &components, value)?,
+            false => f(components.join("/"), &path, value)?,
+        }
+    }
+    nested.visit(f)
+}
@@ -1,68 +1,94 @@
-use super::{JsonReader, JsonSource};
+use super::{
+    EntryCallback, EntryFilter, EntryOrder, JsonReader, JsonSource, SourceCallback,
+    file::{stream_entries, stream_entries_at},
+};
+use crate::compression::decompress;
 use eyre::{Result, eyre};
 use serde_json::Value;
 use std::{
-    io::{BufRead, BufReader, Read, Stdin, stdin},
+    io::{BufRead, BufReader, stdin},
+    path::Path,
     sync::{Arc, Mutex},
 };
 
 #[derive(Clone)]
 pub struct InputStdin {
-    reader: Arc<Mutex<BufReader<Stdin>>>,
+    /// Opened on first use, so stdin isn't read while the command line is still being parsed
+    reader: Arc<Mutex<Option<Box<dyn BufRead + Send>>>>,
 }
 
 impl InputStdin {
     pub fn new() -> Self {
         Self {
-            reader: Arc::new(Mutex::new(BufReader::new(stdin()))),
+            reader: Arc::new(Mutex::new(None)),
         }
     }
-}
 
-impl JsonSource for InputStdin {
-    fn get_entries(&self, _sort: bool) -> Vec<(String, Value)> {
-        let mut entries = Vec::new();
-        let reader = &mut self.reader.lock().expect("Failed to lock stdin reader");
-        let mut buf = String::new();
-        while reader.read_line(&mut buf).is_ok() {
-            if buf.is_empty() {
-                break;
-            }
-            match serde_json::from_str(&buf) {
-                Ok(entry) => entries.push(entry),
-                Err(e) => {
-                    log::error!("Error parsing JSON: {}", e);
-                }
-            }
-            buf.clear();
+    /// Runs `f` on the stdin reader, detecting its compression on the first call
+    fn with_reader<T>(&self, f: impl FnOnce(&mut (dyn BufRead + Send)) -> Result<T>) -> Result<T> {
+        let mut reader = self.reader.lock().map_err(|e| eyre!("{e}"))?;
+        if reader.is_none() {
+            *reader = Some(decompress(BufReader::new(stdin()), None)?);
         }
-        entries
+        f(reader.as_mut().expect("Stdin reader was just opened"))
     }
+}
 
+impl JsonSource for InputStdin {
     fn read_entry(&self) -> Result<(String, Value)> {
         let mut buf = String::new();
         self.read_line(&mut buf)?;
         Ok(serde_json::from_str(&buf)?)
     }
+
+    fn visit_entries(
+        &self,
+        _order: EntryOrder,
+        _recursive: bool,
+        _filter: &EntryFilter,
+        f: &mut SourceCallback,
+    ) -> Result<()> {
+        self.with_reader(|reader| {
+            let mut buf = String::new();
+            while reader.read_line(&mut buf)? > 0 {
+                match serde_json::from_str::<(String, Value)>(&buf) {
+                    Ok((key, value)) => f(key, Path::new("-"), value)?,
+                    Err(e) => log::error!("Error parsing JSON: {}", e),
+                }
+                buf.clear();
+            }
+            Ok(())
+        })
+    }
 }
 
 impl JsonReader for InputStdin {
     fn get_object(&self) -> Result<Value> {
         let mut buffer = String::new();
-        stdin().read_to_string(&mut buffer)?;
+        self.with_reader(|reader| Ok(reader.read_to_string(&mut buffer)?))?;
         Ok(serde_json::from_str(&buffer)?)
     }
 
     fn read_line(&self, buf: &mut String) -> Result<()> {
-        let mut reader = self.reader.lock().map_err(|e| eyre!("{e}"))?;
-        reader.read_line(buf)?;
-        Ok(())
+        self.with_reader(|reader| Ok(reader.read_line(buf).map(drop)?))
+    }
+
+    fn for_each_entry(&self, f: &mut EntryCallback) -> Result<()> {
+        self.with_reader(|reader| stream_entries(reader, f))
+    }
+
+    fn for_each_entry_at(&self, at: &str, f: &mut EntryCallback) -> Result<Value> {
+        self.with_reader(|reader| stream_entries_at(reader, at, f))
+    }
+
+    fn source_name(&self) -> String {
+        "-".to_string()
     }
 }
 
 impl std::io::Read for InputStdin {
     fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
-        let mut reader = self.reader.lock().map_err(|_| std::io::ErrorKind::Other)?;
-        reader.read(buf)
+        self.with_reader(|reader| Ok(reader.read(buf)?))
+            .map_err(std::io::Error::other)
     }
 }
@@ -1,5 +1,11 @@
+/// Detect and decode compressed streams
+pub mod compression;
+/// Encode keys as safe, reversible filenames
+pub mod filename;
 /// Read from inputs
 pub mod input;
+/// List written entries for lossless round-trips
+pub mod manifest;
 /// Write to outputs
 pub mod output;
 /// Process data
@@ -1,9 +1,23 @@
-use clap::{Parser, Subcommand};
+use clap::{ArgAction, CommandFactory, Parser, Subcommand, error::ErrorKind};
 use eyre::{Result, eyre};
 use jsrmx::{
-    input::{InputDirectory, JsonReaderInput, JsonSourceInput},
-    output::{JsonAppendableOutput, JsonWritableOutput},
-    processor::{BundlerBuilder, Json, UnbundlerBuilder},
+    compression::Compressor,
+    filename::{self, CollisionPolicy},
+    input::{EntryFilter, EntryOrder, JsonReaderInput, JsonSourceInput},
+    output::{
+        ArchiveFormat, Indent, JsonAppendableOutput, JsonFormat, JsonFormatBuilder,
+        JsonWritableOutput, ObjectWriter,
+    },
+    processor::{
+        Assignment, BundlerBuilder, Envelope, FieldPath, Json, KeyCase, KeyOrder, KeySort,
+        NameTemplate, Origin, Pseudonymizer, Rename, Transforms, UnbundlerBuilder, field_to_name,
+    },
+};
+use regex::Regex;
+use serde_json::Value;
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
 };
 
 #[derive(Parser)]
@@ -13,19 +27,97 @@ struct Cli {
     #[command(subcommand)]
     command: Commands,
 
-    /// Fields to drop from the output
+    /// Fields to keep in the output, dropping everything else, like `id,type,attributes.title`
+    #[arg(long, global = true, value_delimiter = ',')]
+    keep: Option<Vec<FieldPath>>,
+
+    /// Fields to drop from the output, like `objects[*].attributes.**._secret`
+    #[arg(long, global = true, value_delimiter = ',')]
+    drop: Option<Vec<FieldPath>>,
+
+    /// Fields whose values are replaced with `[REDACTED]`
+    #[arg(long, global = true, value_delimiter = ',')]
+    redact: Option<Vec<FieldPath>>,
+
+    /// Fields whose values are replaced with a keyed hash, so equal values get equal tokens
+    #[arg(long, global = true, value_delimiter = ',')]
+    pseudonymize: Option<Vec<FieldPath>>,
+
+    /// Secret key for `--pseudonymize`. Prefer `JSRMX_KEY` or `--key-file`, which stay out of
+    /// the process list and shell history
+    #[arg(long, global = true, env = "JSRMX_KEY", hide_env_values = true)]
+    key: Option<String>,
+
+    /// File to read the `--pseudonymize` key from, without its trailing newline. Used
+    /// instead of `--key` when both are given
+    #[arg(long, global = true)]
+    key_file: Option<PathBuf>,
+
+    /// Fields to move to another path, like `_id=id,attributes.title=title`
     #[arg(long, global = true, value_delimiter = ',')]
-    drop: Option<Vec<String>>,
+    rename: Option<Vec<Rename>>,
+
+    /// Convert every output key to `snake`, `camel` or `kebab` case
+    #[arg(long, global = true)]
+    key_case: Option<KeyCase>,
+
+    /// Field to set on every record to a JSON value, like `migrationVersion=3`. Can be repeated
+    #[arg(long, global = true, value_parser = Assignment::constant)]
+    set: Option<Vec<Assignment>>,
+
+    /// Fields to set to where each record came from: `filename`, `index`, `key` or `now`
+    #[arg(long, global = true, value_delimiter = ',', value_parser = Assignment::from_context)]
+    set_from: Option<Vec<Assignment>>,
+
+    /// Write RFC 8785 canonical JSON, ignoring the other formatting options
+    #[arg(long, global = true, default_value_t = false)]
+    canonical: bool,
+
+    /// Spaces to indent pretty-printed output by, or `tab`
+    #[arg(long, global = true, default_value = "2")]
+    indent: Indent,
+
+    /// Keep pretty-printed objects and arrays on one line when they fit in this many columns
+    #[arg(long, global = true, default_value_t = 0)]
+    inline_width: usize,
+
+    /// Escape non-ASCII characters as `\uXXXX`
+    #[arg(long, global = true, default_value_t = false)]
+    ensure_ascii: bool,
+
+    /// Don't end each output file with a newline
+    #[arg(long = "no-trailing-newline", global = true, action = ArgAction::SetFalse)]
+    trailing_newline: bool,
+
+    /// Keys to write first in every output object, like `id,type,attributes`
+    #[arg(long, global = true, value_delimiter = ',')]
+    key_order: Option<Vec<String>>,
+
+    /// Order of the other keys in every output object: `preserve`, `alpha` or `natural`
+    #[arg(long, global = true, default_value = "preserve")]
+    sort_keys: KeySort,
 }
 
 #[derive(Subcommand)]
 enum Commands {
     /// Merges multiple single-object <dir>/${key}.json files into one json object.
     Merge {
+        /// Rebuild a JSON array in filename order instead of an object, with `2` before `10`
+        #[arg(short, long, default_value_t = false)]
+        array: bool,
         /// Compact JSON output
         #[arg(short, long, conflicts_with = "pretty", default_value_t = false)]
         compact: bool,
-        /// Target input directory
+        /// Compress output as `gzip`, `zstd` or `bzip2`, with an optional `:level`
+        #[arg(long)]
+        compress: Option<Compressor>,
+        /// Envelope sidecar file from `split --at` to place the merged value back into
+        #[arg(short, long)]
+        envelope: Option<PathBuf>,
+        /// Skip files matching these comma-separated globs
+        #[arg(short = 'x', long, value_delimiter = ',')]
+        exclude: Option<Vec<String>>,
+        /// Target input directory or archive
         input: JsonSourceInput,
         /// Output filename or `-` for stdout
         #[arg(default_value = "-")]
@@ -33,21 +125,51 @@ enum Commands {
         /// Only split keys matching regex filter
         #[arg(short, long)]
         filter: Option<String>,
+        /// Skip symlinked files and directories instead of following them
+        #[arg(long = "no-follow-symlinks", action = ArgAction::SetFalse)]
+        follow_symlinks: bool,
+        /// Read hidden files and directories, which are skipped by default
+        #[arg(long, default_value_t = false)]
+        hidden: bool,
+        /// Only read files matching these comma-separated globs
+        #[arg(short, long, value_delimiter = ',')]
+        include: Option<Vec<String>>,
         /// Pretty-print output files
         #[arg(short, long, default_value_t = true)]
         pretty: bool,
-        /// Alphabetically sort object keys
+        /// Rebuild subdirectories as nested objects
+        #[arg(short, long, default_value_t = false)]
+        recursive: bool,
+        /// Sort archive members by name too, alphabetically
         #[arg(short, long, default_value_t = false)]
         sort: bool,
+        /// Fail on the first file that can't be read as JSON instead of skipping it
+        #[arg(long, default_value_t = false)]
+        strict: bool,
     },
     /// Splits single JSON object into multiple json objects.
     Split {
+        /// Stream an archive of this format (`tar`) to stdout instead of plain JSON, with `-` as output
+        #[arg(long)]
+        archive: Option<ArchiveFormat>,
+        /// Field path of a nested object or array to split instead of the root
+        #[arg(short, long)]
+        at: Option<String>,
         /// Compact JSON output
         #[arg(short, long, conflicts_with = "pretty", default_value_t = false)]
         compact: bool,
+        /// Compress output as `gzip`, `zstd` or `bzip2`, with an optional `:level`
+        #[arg(long)]
+        compress: Option<Compressor>,
+        /// Sidecar file to save the document surrounding the `--at` value into
+        #[arg(short, long, requires = "at")]
+        envelope: Option<PathBuf>,
         /// Input filename or `-` for stdin
         #[arg(default_value = "-")]
         input: JsonReaderInput,
+        /// Write a `_manifest.json` listing each entry's position, file and checksum
+        #[arg(short, long, default_value_t = false)]
+        manifest: bool,
         /// Target output directory or `-` for stdout
         #[arg(default_value = "-")]
         output: JsonWritableOutput,
@@ -57,41 +179,89 @@ enum Commands {
         /// Pretty-print output files
         #[arg(short, long, default_value_t = true)]
         pretty: bool,
+        /// Levels of nested objects to split into subdirectories
+        #[arg(long, default_value_t = 1)]
+        depth: usize,
+        /// Field to name array elements by, instead of their index
+        #[arg(short, long)]
+        key_field: Option<FieldPath>,
+        /// Template for output keys, like `{type}/{id}` or `{@key|slug}`
+        #[arg(long, conflicts_with = "key_field")]
+        name_template: Option<NameTemplate>,
     },
     /// Bundles multiple <dir>/*.json files into one ndjson file
     Bundle {
-        /// Target input directory
-        dir: InputDirectory,
+        /// Target input directory or archive
+        dir: JsonSourceInput,
         /// Output filename or `-` for stdout
         #[arg(default_value = "-")]
         output: JsonAppendableOutput,
+        /// Compress output as `gzip`, `zstd` or `bzip2`, with an optional `:level`
+        #[arg(long)]
+        compress: Option<Compressor>,
         /// String-escaped nested JSON fields to escape
         #[arg(short, long, value_delimiter = ',')]
-        escape: Option<Vec<String>>,
+        escape: Option<Vec<FieldPath>>,
+        /// Skip files matching these comma-separated globs
+        #[arg(short = 'x', long, value_delimiter = ',')]
+        exclude: Option<Vec<String>>,
+        /// Skip symlinked files and directories instead of following them
+        #[arg(long = "no-follow-symlinks", action = ArgAction::SetFalse)]
+        follow_symlinks: bool,
+        /// Read hidden files and directories, which are skipped by default
+        #[arg(long, default_value_t = false)]
+        hidden: bool,
+        /// Only read files matching these comma-separated globs
+        #[arg(short, long, value_delimiter = ',')]
+        include: Option<Vec<String>>,
+        /// Bundle files in subdirectories too, named by their relative path
+        #[arg(short, long, default_value_t = false)]
+        recursive: bool,
+        /// Fail on the first file that can't be read as JSON instead of skipping it
+        #[arg(long, default_value_t = false)]
+        strict: bool,
     },
     /// Unbundle single [input] file into multiple json objects
     Unbundle {
+        /// Stream an archive of this format (`tar`) to stdout instead of plain JSON, with `-` as output
+        #[arg(long)]
+        archive: Option<ArchiveFormat>,
         /// Compact JSON output
         #[arg(short, long, conflicts_with = "pretty", default_value_t = false)]
         compact: bool,
+        /// Compress output as `gzip`, `zstd` or `bzip2`, with an optional `:level`
+        #[arg(long)]
+        compress: Option<Compressor>,
         /// Input filename or `-` for stdin
         #[arg(default_value = "-")]
         input: JsonReaderInput,
+        /// Write a `_manifest.json` listing each record's position, file and checksum
+        #[arg(short, long, default_value_t = false)]
+        manifest: bool,
         /// Target output directory or `-` for stdout
         #[arg(default_value = "-")]
         output: JsonWritableOutput,
-        /// List of field names to read for filename, uses first non-null value
+        /// List of field names to read for filename, uses first non-null, non-empty value
         #[arg(short, long, value_delimiter = ',')]
-        name: Option<Vec<String>>,
+        name: Option<Vec<FieldPath>>,
         /// Field name to append before the file extension
         #[arg(short = 't', long = "type")]
-        type_field: Option<String>,
+        type_field: Option<FieldPath>,
+        /// Template for output names, like `{type}/{attributes.title|slug}-{id}`
+        #[arg(long, conflicts_with_all = ["name", "type_field"])]
+        name_template: Option<NameTemplate>,
+        /// What to do when records have the same name: `error`, `suffix`, `skip` or `overwrite`
+        #[arg(long, default_value = "error")]
+        on_collision: CollisionPolicy,
         /// Pretty-print output objects
         #[arg(short, long, default_value_t = true)]
         pretty: bool,
+        /// Field path of an array of records inside a single JSON document
+        #[arg(short, long)]
+        records_at: Option<String>,
         /// String-escaped nested JSON fields to unescape
         #[arg(short, long, value_delimiter = ',')]
-        unescape: Option<Vec<String>>,
+        unescape: Option<Vec<FieldPath>>,
     },
 }
 
@@ -108,92 +278,302 @@ fn main() -> Result<()> {
         log::error!("{}", panic);
     }));
 
+    let format = JsonFormatBuilder::new()
+        .canonical(cli.canonical)
+        .indent(cli.indent)
+        .inline_width(cli.inline_width)
+        .ensure_ascii(cli.ensure_ascii)
+        .trailing_newline(cli.trailing_newline)
+        .key_order(KeyOrder::new(cli.sort_keys, cli.key_order));
+    let pseudonymizer = match (cli.pseudonymize, cli.key, cli.key_file) {
+        (None, _, _) => None,
+        (Some(fields), _, Some(path)) => {
+            let mut key = std::fs::read(&path)
+                .map_err(|e| eyre!("Error reading key file {}: {e}", path.display()))?;
+            while key
+                .last()
+                .is_some_and(|&byte| byte == b'\n' || byte == b'\r')
+            {
+                key.pop();
+            }
+            Some(Pseudonymizer::new(fields, key))
+        }
+        (Some(fields), Some(key), None) => Some(Pseudonymizer::new(fields, key)),
+        (Some(_), None, None) => {
+            return Err(eyre!(
+                "--pseudonymize needs a key from `JSRMX_KEY`, `--key-file` or `--key`"
+            ));
+        }
+    };
+    let assignments = match (cli.set, cli.set_from) {
+        (Some(mut set), Some(set_from)) => {
+            set.extend(set_from);
+            Some(set)
+        }
+        (set, set_from) => set.or(set_from),
+    };
+    let transforms = Transforms::new()
+        .keep(cli.keep)
+        .drop(cli.drop)
+        .redact(cli.redact)
+        .pseudonymizer(pseudonymizer)
+        .renames(cli.rename)
+        .key_case(cli.key_case)
+        .assignments(assignments);
     match cli.command {
         Commands::Merge {
+            array,
             compact,
+            compress,
+            envelope,
+            exclude,
             input,
             output,
             filter,
+            follow_symlinks,
+            hidden,
+            include,
             pretty,
+            recursive,
             sort,
+            strict,
         } => {
-            let entries = input.get_entries(sort);
-            let json = Json::from(entries)
-                .filter(filter.as_ref())?
-                .drop(cli.drop.as_ref())
-                .value();
-            if pretty && !compact {
+            output.configure(format.pretty(pretty && !compact).build(), compress)?;
+            let filter = filter.as_deref().map(Regex::new).transpose()?;
+            let entry_filter = EntryFilter::new()
+                .include(include)?
+                .exclude(exclude)?
+                .follow_symlinks(follow_symlinks)
+                .hidden(hidden)
+                .strict(strict);
+            let output = output.read().expect("Error acquiring read lock on output");
+            let envelope = envelope.map(|path| Envelope::read(&path)).transpose()?;
+            // The envelope is written around the merged value, so buffer it instead of streaming
+            let mut buffer = Vec::new();
+            let mut writer = match (array, &envelope) {
+                (true, None) => output.array_writer()?,
+                (false, None) => output.object_writer()?,
+                (true, Some(_)) => {
+                    ObjectWriter::array(Box::new(&mut buffer), JsonFormat::default())
+                }
+                (false, Some(_)) => ObjectWriter::new(Box::new(&mut buffer), JsonFormat::default()),
+            };
+            let mut index = 0;
+            // Files with the same stem in different subdirectories or with different extensions
+            // would otherwise write the same key twice
+            let mut written = HashSet::new();
+            let mut merge_entry = |key: String, path: &Path, value: Value| {
+                index += 1;
+                if filter.as_ref().is_some_and(|regex| !regex.is_match(&key)) {
+                    return Ok(());
+                }
+                let origin_key = key.clone();
+                let path = path.to_string_lossy();
+                let origin = Origin {
+                    filename: Some(&path),
+                    index: index - 1,
+                    key: Some(&origin_key),
+                };
+                if array {
+                    let value = transforms.apply(Json::from(value), &origin)?.value();
+                    return writer
+                        .write_element(&value)
+                        .map_err(|e| eyre!("Error writing to output: {e}"));
+                }
+                // Field paths are relative to the merged object, so apply them to a single-entry object
+                let json = transforms.select(Json::from(vec![(key, value)]))?.value();
+                if let Value::Object(entry) = json {
+                    for (key, value) in entry {
+                        // Entry names come from filenames, so only their values are converted and set
+                        if !written.insert(key.clone()) {
+                            return Err(eyre!(
+                                "Entry `{key}` from {path} was already merged from another file \
+                                 with the same name"
+                            ));
+                        }
+                        let value = transforms.convert(Json::from(value), &origin)?.value();
+                        writer
+                            .write_entry(&key, &value)
+                            .map_err(|e| eyre!("Error writing to output: {e}"))?;
+                    }
+                }
+                Ok(())
+            };
+            // Array elements must keep their order, so always sort archive members too, with
+            // numbered names like `2` before `10`
+            let order = match (array, sort) {
+                (true, _) => EntryOrder::Natural,
+                (false, true) => EntryOrder::Sorted,
+                (false, false) => EntryOrder::Default,
+            };
+            input.visit_entries(order, recursive, &entry_filter, &mut merge_entry)?;
+            writer
+                .finish()
+                .map_err(|e| eyre!("Error writing to output: {e}"))?;
+            if let Some(envelope) = envelope {
                 output
-                    .write()
-                    .expect("Error acquiring write lock on output")
-                    .set_pretty(true);
+                    .append(envelope.wrap(serde_json::from_slice(&buffer)?)?)
+                    .map_err(|e| eyre!("Error writing to output: {e}"))?;
             }
             output
-                .read()
-                .expect("Error acquiring read lock on output")
-                .append(json)
+                .finish()
                 .map_err(|e| eyre!("Error writing to output: {e}"))
         }
         Commands::Split {
+            archive,
+            at,
             compact,
+            compress,
+            envelope,
             input,
+            manifest,
             output,
             filter,
             pretty,
+            depth,
+            key_field,
+            name_template,
         } => {
-            if pretty && !compact {
+            let output = archive_output(archive, output);
+            output.configure(format.pretty(pretty && !compact).build(), compress)?;
+            output
+                .write()
+                .expect("Error acquiring write lock on output")
+                .set_manifest(manifest);
+            let filter = filter.as_deref().map(Regex::new).transpose()?;
+            let output = output.read().expect("Error acquiring read lock on output");
+            let source_name = input.source_name();
+            let mut index = 0;
+            let mut split_entry = |key: String, value: Value| {
+                index += 1;
+                if filter.as_ref().is_some_and(|regex| !regex.is_match(&key)) {
+                    return Ok(());
+                }
+                let name = match &name_template {
+                    Some(template) => template.render(&value, &key, index - 1)?,
+                    None => filename::encode(
+                        &key_field
+                            .as_ref()
+                            .and_then(|field| field_to_name(&value, field))
+                            .unwrap_or_else(|| key.clone()),
+                    ),
+                };
+                let origin = Origin {
+                    filename: Some(&source_name),
+                    index: index - 1,
+                    key: Some(&key),
+                };
+                let value = transforms.apply(Json::from(value), &origin)?.value();
+                // Name each file after the original key and the nested keys below it
+                let entries = Json::from(vec![(name.clone(), value)])
+                    .nested_entries(depth)?
+                    .list()
+                    .into_iter()
+                    .map(|(path, value)| {
+                        let keys = std::iter::once(key.clone())
+                            .chain(path[name.len()..].split('/').skip(1).map(filename::decode))
+                            .collect();
+                        (keys, path, value)
+                    })
+                    .collect();
                 output
-                    .write()
-                    .expect("Error acquiring write lock on output")
-                    .set_pretty(true);
+                    .write_keyed_entries(entries)
+                    .map_err(|e| eyre!("Error writing to output: {e}"))
             };
-            let object = input.get_object().expect("Error reading input: {input:?}");
-            let entries = Json::from(object)
-                .entries()?
-                .filter(filter.as_ref())?
-                .drop(cli.drop.as_ref())
-                .list();
+            match at {
+                Some(at) => {
+                    let document = input.for_each_entry_at(&at, &mut split_entry)?;
+                    if let Some(path) = envelope {
+                        Envelope::new(at, document).write(&path)?;
+                    }
+                }
+                None => input.for_each_entry(&mut split_entry)?,
+            }
             output
-                .read()
-                .expect("Error acquiring read lock on output")
-                .write_entries(entries)
+                .finish()
                 .map_err(|e| eyre!("Error writing to output: {e}"))
         }
         Commands::Bundle {
+            compress,
             dir,
             escape,
+            exclude,
+            follow_symlinks,
+            hidden,
+            include,
             output,
+            recursive,
+            strict,
         } => {
+            // Bundles are NDJSON, so records are never pretty-printed
+            output.configure(format.build(), compress)?;
+            let filter = EntryFilter::new()
+                .include(include)?
+                .exclude(exclude)?
+                .follow_symlinks(follow_symlinks)
+                .hidden(hidden)
+                .strict(strict);
             let bundler = BundlerBuilder::new(dir, output)
                 .escape_fields(escape)
-                .drop_fields(cli.drop)
+                .transforms(transforms)
+                .filter(filter)
+                .recursive(recursive)
                 .build();
 
             bundler.bundle()
         }
         Commands::Unbundle {
+            archive,
             compact,
+            compress,
             input,
+            manifest,
             name,
             output,
             pretty,
+            records_at,
             type_field,
+            name_template,
+            on_collision,
             unescape,
         } => {
-            if pretty && !compact {
-                output
+            let output = archive_output(archive, output);
+            output.configure(format.pretty(pretty && !compact).build(), compress)?;
+            {
+                let mut output = output
                     .write()
-                    .expect("Error acquiring write lock on output")
-                    .set_pretty(true);
+                    .expect("Error acquiring write lock on output");
+                output.set_collision_policy(on_collision);
+                output.set_manifest(manifest);
             }
             let unbundler = UnbundlerBuilder::new(input, output)
                 .unescape_fields(unescape)
-                .drop_fields(cli.drop)
+                .transforms(transforms)
                 .filename(name)
                 .type_field(type_field)
+                .name_template(name_template)
+                .records_at(records_at)
                 .build();
 
             unbundler.unbundle()
         }
     }
 }
+
+/// Streams an `--archive` to stdout, which can't be combined with any other output
+fn archive_output(
+    archive: Option<ArchiveFormat>,
+    output: JsonWritableOutput,
+) -> JsonWritableOutput {
+    match archive {
+        None => output,
+        Some(format) if output.is_stdout() => JsonWritableOutput::archive_stream(format),
+        Some(_) => Cli::command()
+            .error(
+                ErrorKind::ArgumentConflict,
+                "--archive streams to stdout, so the output must be `-`. Name the output \
+                 `.tar` or `.zip` to write an archive file instead",
+            )
+            .exit(),
+    }
+}
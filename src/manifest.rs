@@ -0,0 +1,131 @@
+use eyre::{Result, eyre};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{collections::HashMap, io::Read};
+
+/// Name of the manifest written at the root of a directory or archive output
+pub const MANIFEST_FILE: &str = "_manifest.json";
+
+/// Lists the entries written by `split` or `unbundle`, so `merge` and `bundle` can read
+/// them back in their original order and check that none of them have changed.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Manifest {
+    pub entries: Vec<ManifestEntry>,
+    /// Index of the entry written to each path
+    #[serde(skip)]
+    paths: HashMap<String, usize>,
+    /// Number of entries pushed so far
+    #[serde(skip)]
+    pushed: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ManifestEntry {
+    /// Key of the entry in the input, or the first part of the name of an unbundled record
+    pub key: String,
+    /// Keys of the nested objects below `key` that the file holds, from `split --depth`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub nested: Vec<String>,
+    /// Position of the entry in the input
+    pub position: usize,
+    /// Path of the file relative to the output root
+    pub path: String,
+    /// SHA-256 of the uncompressed file content
+    pub sha256: String,
+}
+
+impl Manifest {
+    pub fn read<R: Read>(reader: R) -> Result<Self> {
+        serde_json::from_reader(reader).map_err(|e| eyre!("Invalid {MANIFEST_FILE}: {e}"))
+    }
+
+    pub fn to_vec(&self) -> serde_json::Result<Vec<u8>> {
+        let mut content = serde_json::to_vec_pretty(self)?;
+        content.push(b'\n');
+        Ok(content)
+    }
+
+    /// Records the next entry under its key followed by any nested keys, positioned after
+    /// every entry recorded so far.
+    ///
+    /// An earlier entry written to the same path, which only happens when colliding entries
+    /// overwrite each other, is replaced, since its file was overwritten.
+    pub fn push(&mut self, keys: Vec<String>, path: String, content: &[u8]) {
+        let mut keys = keys.into_iter();
+        let entry = ManifestEntry {
+            key: keys.next().unwrap_or_default(),
+            nested: keys.collect(),
+            position: self.pushed,
+            path,
+            sha256: sha256(content),
+        };
+        self.pushed += 1;
+        match self.paths.get(&entry.path) {
+            Some(&index) => self.entries[index] = entry,
+            None => {
+                self.paths.insert(entry.path.clone(), self.entries.len());
+                self.entries.push(entry);
+            }
+        }
+    }
+
+    /// Entries in their original order
+    pub fn ordered(mut self) -> Vec<ManifestEntry> {
+        self.entries.sort_by_key(|entry| entry.position);
+        self.entries
+    }
+}
+
+impl ManifestEntry {
+    /// The key followed by the nested keys, naming the entry when it is read back
+    pub fn keys(&self) -> Vec<String> {
+        std::iter::once(self.key.clone())
+            .chain(self.nested.iter().cloned())
+            .collect()
+    }
+
+    /// Fails when `content` is not what was originally written
+    pub fn verify(&self, content: &[u8]) -> Result<()> {
+        match sha256(content) == self.sha256 {
+            true => Ok(()),
+            false => Err(eyre!(
+                "Checksum mismatch for {}, it changed since {MANIFEST_FILE} was written. \
+                 Remove the manifest to read the files as they are",
+                self.path
+            )),
+        }
+    }
+}
+
+/// Lowercase hex SHA-256 digest of `content`
+pub fn sha256(content: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(content))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn records_positions_and_checksums() -> Result<()> {
+        let keys = |keys: &[&str]| keys.iter().map(|key| key.to_string()).collect();
+        let mut manifest = Manifest::default();
+        manifest.push(keys(&["bravo"]), "bravo.json".into(), b"2");
+        manifest.push(keys(&["alpha", "a/b"]), "1/a%2Fb.json".into(), b"1");
+        manifest.push(keys(&["charlie"]), "charlie.json".into(), b"3");
+        manifest.push(keys(&["charlie"]), "charlie.json".into(), b"4");
+        manifest.entries.reverse();
+
+        let read = Manifest::read(manifest.to_vec()?.as_slice())?;
+        let entries = read.ordered();
+        assert_eq!(entries[0].key, "bravo");
+        assert_eq!(entries[1].keys(), ["alpha", "a/b"]);
+        assert_eq!(entries[1].position, 1);
+        entries[1].verify(b"1")?;
+        assert!(entries[1].verify(b"11").is_err());
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[2].position, 3);
+        entries[2].verify(b"4")?;
+        Ok(())
+    }
+}
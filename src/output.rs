@@ -1,25 +1,101 @@
+mod archive;
 mod directory;
 mod file;
+mod format;
+mod ledger;
+mod object;
 mod stream;
+pub use archive::ArchiveFormat;
+use archive::ArchiveOutput;
 use directory::DirectoryOutput;
-use eyre::{eyre, Report, Result};
+use eyre::{Report, Result, eyre};
 use file::FileOutput;
+pub use format::{Indent, JsonFormat, JsonFormatBuilder};
+pub use object::ObjectWriter;
 use stream::StreamOutput;
 
+use crate::{compression::Compressor, filename::CollisionPolicy};
 use serde_json::Value;
 use std::{
+    io::Write,
     ops::Deref,
     path::PathBuf,
-    sync::{Arc, RwLock},
+    sync::{Arc, MutexGuard, RwLock},
 };
 
 pub trait Appendable: Writeable {
     fn append(&self, content: Value) -> std::io::Result<()>;
+    /// Starts a single JSON object that is written incrementally, one entry at a time
+    fn object_writer(&self) -> std::io::Result<ObjectWriter<'_>>;
+    /// Starts a single JSON array that is written incrementally, one element at a time
+    fn array_writer(&self) -> std::io::Result<ObjectWriter<'_>>;
 }
 
 pub trait Writeable: Send + Sync {
-    fn set_pretty(&mut self, pretty: bool);
+    /// Formats everything written from now on
+    fn set_format(&mut self, format: JsonFormat);
+    /// Compresses everything written from now on
+    fn set_compression(&mut self, compressor: Compressor) -> std::io::Result<()>;
+    /// Sets how everything is formatted and compressed, before anything is written
+    fn configure(
+        &mut self,
+        format: JsonFormat,
+        compressor: Option<Compressor>,
+    ) -> std::io::Result<()> {
+        self.set_format(format);
+        match compressor {
+            Some(compressor) => self.set_compression(compressor),
+            None => Ok(()),
+        }
+    }
+    /// Decides what happens to entries named like an earlier one, when names are filenames
+    fn set_collision_policy(&mut self, _policy: CollisionPolicy) {}
+    /// Number of entries that collided with an earlier one so far
+    fn collisions(&self) -> usize {
+        0
+    }
+    /// Lists every entry written in a manifest, saved by `finish`
+    fn set_manifest(&mut self, manifest: bool) {
+        if manifest {
+            log::warn!("Manifests are only written to directory and archive outputs");
+        }
+    }
+    /// Whether everything is written to stdout
+    fn is_stdout(&self) -> bool {
+        false
+    }
     fn write_entries(&self, entries: Vec<(String, Value)>) -> std::io::Result<()>;
+    /// Writes entries named differently from the keys they came from, which the manifest
+    /// records instead of their names
+    fn write_keyed_entries(
+        &self,
+        entries: Vec<(Vec<String>, String, Value)>,
+    ) -> std::io::Result<()> {
+        self.write_entries(
+            entries
+                .into_iter()
+                .map(|(_, name, value)| (name, value))
+                .collect(),
+        )
+    }
+    /// Writes anything that has to come after the last entry and ends any compressed stream,
+    /// so a failure there is reported instead of being lost when the output is dropped
+    fn finish(&self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Holds a writer lock for as long as an `ObjectWriter` is open
+struct LockedWriter<'a, W: Write>(MutexGuard<'a, W>);
+
+impl<W: Write> Write for LockedWriter<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.0.flush()
+    }
 }
 
 #[derive(Clone)]
@@ -31,7 +107,7 @@ impl std::str::FromStr for JsonAppendableOutput {
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         match s {
             "-" => Ok(JsonAppendableOutput(Arc::new(RwLock::new(
-                StreamOutput::new(false),
+                StreamOutput::new(),
             )))),
             s => {
                 let path = PathBuf::from(s);
@@ -39,12 +115,12 @@ impl std::str::FromStr for JsonAppendableOutput {
                     Err(eyre!("Cannot append to a directory output: {s}"))
                 } else if path.is_file() {
                     Ok(JsonAppendableOutput(Arc::new(RwLock::new(
-                        FileOutput::new(path, false),
+                        FileOutput::new(path),
                     ))))
                 } else {
                     log::info!("Creating file: {}", &path.display());
                     Ok(JsonAppendableOutput(Arc::new(RwLock::new(
-                        FileOutput::new(path, false),
+                        FileOutput::new(path),
                     ))))
                 }
             }
@@ -52,6 +128,16 @@ impl std::str::FromStr for JsonAppendableOutput {
     }
 }
 
+impl JsonAppendableOutput {
+    /// Sets how everything is formatted and compressed, before anything is written
+    pub fn configure(&self, format: JsonFormat, compressor: Option<Compressor>) -> Result<()> {
+        self.write()
+            .map_err(|_| eyre!("Error acquiring write lock on output"))?
+            .configure(format, compressor)?;
+        Ok(())
+    }
+}
+
 impl Deref for JsonAppendableOutput {
     type Target = Arc<RwLock<dyn Appendable>>;
 
@@ -69,22 +155,26 @@ impl std::str::FromStr for JsonWritableOutput {
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         match s {
             "-" => Ok(JsonWritableOutput(Arc::new(RwLock::new(
-                StreamOutput::new(false),
+                StreamOutput::new(),
             )))),
             s => {
                 let path = PathBuf::from(s);
-                if path.is_dir() | path.extension().is_none() {
+                if let Some((format, compressor)) = ArchiveFormat::from_path(&path) {
                     Ok(JsonWritableOutput(Arc::new(RwLock::new(
-                        DirectoryOutput::new(path, false),
+                        ArchiveOutput::new(format, Some(path), compressor),
+                    ))))
+                } else if path.is_dir() | path.extension().is_none() {
+                    Ok(JsonWritableOutput(Arc::new(RwLock::new(
+                        DirectoryOutput::new(path),
                     ))))
                 } else if path.is_file() {
                     Ok(JsonWritableOutput(Arc::new(RwLock::new(FileOutput::new(
-                        path, false,
+                        path,
                     )))))
                 } else {
                     log::info!("Creating file: {}", &path.display());
                     Ok(JsonWritableOutput(Arc::new(RwLock::new(FileOutput::new(
-                        path, false,
+                        path,
                     )))))
                 }
             }
@@ -92,6 +182,30 @@ impl std::str::FromStr for JsonWritableOutput {
     }
 }
 
+impl JsonWritableOutput {
+    /// Sets how everything is formatted and compressed, before anything is written
+    pub fn configure(&self, format: JsonFormat, compressor: Option<Compressor>) -> Result<()> {
+        self.write()
+            .map_err(|_| eyre!("Error acquiring write lock on output"))?
+            .configure(format, compressor)?;
+        Ok(())
+    }
+
+    /// Streams entries to stdout as members of an archive
+    pub fn archive_stream(format: ArchiveFormat) -> Self {
+        JsonWritableOutput(Arc::new(RwLock::new(ArchiveOutput::new(
+            format, None, None,
+        ))))
+    }
+
+    /// Whether everything is written to stdout
+    pub fn is_stdout(&self) -> bool {
+        self.read()
+            .expect("Error acquiring read lock on output")
+            .is_stdout()
+    }
+}
+
 impl Deref for JsonWritableOutput {
     type Target = Arc<RwLock<dyn Writeable>>;
 
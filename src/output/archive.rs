@@ -0,0 +1,217 @@
+use super::{
+    JsonFormat, Writeable,
+    ledger::{KeyedEntry, Ledger, keyed},
+};
+use crate::{
+    compression::{Compression, Compressor, Encoder},
+    filename::CollisionPolicy,
+    manifest::MANIFEST_FILE,
+};
+use eyre::{Report, eyre};
+use serde_json::Value;
+use std::{
+    fs::File,
+    io::{BufWriter, Write, stdout},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Archive formats that entries can be written into
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ArchiveFormat {
+    Tar,
+    Zip,
+}
+
+impl ArchiveFormat {
+    /// Recognizes `.tar`, `.tgz`, `.tar.gz`, `.tar.zst`, `.tar.bz2` and `.zip` paths,
+    /// along with the compression applied to the whole tar stream
+    pub fn from_path(path: &Path) -> Option<(Self, Option<Compressor>)> {
+        let name = path.file_name()?.to_str()?;
+        if name.ends_with(".zip") {
+            Some((Self::Zip, None))
+        } else if name.ends_with(".tar") {
+            Some((Self::Tar, None))
+        } else if name.ends_with(".tgz") {
+            Some((Self::Tar, Some(Compressor::new(Compression::Gzip))))
+        } else {
+            let compressor = Compressor::from_extension(path)?;
+            let stem = Path::new(path.file_stem()?);
+            match stem.extension()? == "tar" {
+                true => Some((Self::Tar, Some(compressor))),
+                false => None,
+            }
+        }
+    }
+}
+
+impl std::str::FromStr for ArchiveFormat {
+    type Err = Report;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "tar" => Ok(Self::Tar),
+            "zip" => Ok(Self::Zip),
+            s => Err(eyre!("Unknown archive format `{s}`, expected tar or zip")),
+        }
+    }
+}
+
+enum ArchiveWriter {
+    Tar(Box<tar::Builder<Encoder<Box<dyn Write + Send>>>>),
+    Zip(Box<zip::ZipWriter<BufWriter<File>>>),
+}
+
+/// Writes each entry as a `{key}.json` member of a tar or zip archive.
+///
+/// The archive is opened on the first write, so compression can still be changed until then.
+#[derive(Clone)]
+pub struct ArchiveOutput {
+    json_format: JsonFormat,
+    format: ArchiveFormat,
+    compressor: Option<Compressor>,
+    /// Archive file, or `None` to stream to stdout
+    path: Option<PathBuf>,
+    writer: Arc<Mutex<Option<ArchiveWriter>>>,
+    /// Members written so far
+    ledger: Ledger,
+}
+
+impl ArchiveOutput {
+    pub fn new(
+        format: ArchiveFormat,
+        path: Option<PathBuf>,
+        compressor: Option<Compressor>,
+    ) -> Self {
+        Self {
+            json_format: JsonFormat::default(),
+            format,
+            compressor,
+            path,
+            writer: Arc::new(Mutex::new(None)),
+            ledger: Ledger::default(),
+        }
+    }
+
+    fn open(&self) -> std::io::Result<ArchiveWriter> {
+        let unsupported =
+            |message: &str| std::io::Error::new(std::io::ErrorKind::InvalidInput, message);
+        match (self.format, &self.path) {
+            (ArchiveFormat::Tar, path) => {
+                let sink: Box<dyn Write + Send> = match path {
+                    Some(path) => {
+                        log::info!("Creating tar archive {}", path.display());
+                        Box::new(BufWriter::new(File::create(path)?))
+                    }
+                    None => Box::new(stdout()),
+                };
+                let sink = Encoder::new(sink, self.compressor)?;
+                Ok(ArchiveWriter::Tar(Box::new(tar::Builder::new(sink))))
+            }
+            (ArchiveFormat::Zip, Some(path)) => {
+                if self.compressor.is_some() {
+                    log::warn!("Zip members are always deflated, ignoring --compress");
+                }
+                log::info!("Creating zip archive {}", path.display());
+                let file = BufWriter::new(File::create(path)?);
+                Ok(ArchiveWriter::Zip(Box::new(zip::ZipWriter::new(file))))
+            }
+            (ArchiveFormat::Zip, None) => Err(unsupported(
+                "Zip archives cannot be streamed to stdout, use tar instead",
+            )),
+        }
+    }
+}
+
+impl Writeable for ArchiveOutput {
+    fn set_format(&mut self, format: JsonFormat) {
+        self.json_format = format;
+    }
+
+    /// Compresses the whole tar stream, zip members are always deflated
+    fn set_compression(&mut self, compressor: Compressor) -> std::io::Result<()> {
+        self.compressor = Some(compressor);
+        Ok(())
+    }
+
+    fn set_collision_policy(&mut self, policy: CollisionPolicy) {
+        self.ledger.set_collision_policy(policy);
+    }
+
+    fn collisions(&self) -> usize {
+        self.ledger.collisions()
+    }
+
+    fn set_manifest(&mut self, manifest: bool) {
+        self.ledger.set_manifest(manifest);
+    }
+
+    fn write_entries(&self, entries: Vec<(String, Value)>) -> std::io::Result<()> {
+        self.write_keyed_entries(keyed(entries))
+    }
+
+    fn write_keyed_entries(&self, entries: Vec<KeyedEntry>) -> std::io::Result<()> {
+        let entries = self.ledger.claim(entries)?;
+
+        let mut guard = self.writer.lock().expect("Failed to get writer lock");
+        if guard.is_none() {
+            *guard = Some(self.open()?);
+        }
+        let writer = guard.as_mut().expect("Archive writer was just opened");
+        for (keys, name, value) in entries {
+            let name = format!("{name}.json");
+            let content = self.json_format.to_document(&value)?.into_bytes();
+            self.ledger.record(keys, name.clone(), &content);
+            append(writer, &name, &content)?;
+        }
+        Ok(())
+    }
+
+    /// Adds the manifest as the last member of the archive, then writes the end of the
+    /// archive and of its compression
+    fn finish(&self) -> std::io::Result<()> {
+        let mut guard = self.writer.lock().expect("Failed to get writer lock");
+        let mut writer = match guard.take() {
+            Some(writer) => writer,
+            None => self.open()?,
+        };
+        if let Some(content) = self.ledger.manifest()? {
+            append(&mut writer, MANIFEST_FILE, &content)?;
+        }
+        match writer {
+            ArchiveWriter::Tar(builder) => builder.into_inner()?.finish().map(drop),
+            ArchiveWriter::Zip(zip) => zip
+                .finish()
+                .map_err(std::io::Error::other)?
+                .into_inner()
+                .map_err(|e| e.into_error())?
+                .sync_all(),
+        }
+    }
+}
+
+fn append(writer: &mut ArchiveWriter, name: &str, content: &[u8]) -> std::io::Result<()> {
+    log::info!("Archiving {name}");
+    match writer {
+        ArchiveWriter::Tar(builder) => {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(content.len() as u64);
+            header.set_mode(0o644);
+            header.set_mtime(
+                SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map_or(0, |time| time.as_secs()),
+            );
+            builder.append_data(&mut header, name, content)
+        }
+        ArchiveWriter::Zip(zip) => {
+            let options = zip::write::SimpleFileOptions::default()
+                .compression_method(zip::CompressionMethod::Deflated)
+                .unix_permissions(0o644);
+            zip.start_file(name, options)
+                .map_err(std::io::Error::other)?;
+            zip.write_all(content)
+        }
+    }
+}
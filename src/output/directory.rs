@@ -1,59 +1,122 @@
-use super::Writeable;
+use super::{
+    JsonFormat, Writeable,
+    ledger::{KeyedEntry, Ledger, keyed},
+};
+use crate::{
+    compression::{Compressor, Encoder},
+    filename::{CollisionPolicy, is_case_insensitive},
+    manifest::MANIFEST_FILE,
+};
 use rayon::prelude::*;
 use serde_json::Value;
 use std::{
-    fs::{create_dir_all, OpenOptions},
-    io::BufWriter,
-    path::PathBuf,
+    fs::{OpenOptions, create_dir_all},
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
 };
 
 #[derive(Clone, Debug)]
 pub struct DirectoryOutput {
-    pretty: bool,
+    format: JsonFormat,
+    compressor: Option<Compressor>,
     pub path: PathBuf,
+    /// Files written so far
+    ledger: Ledger,
 }
 
 impl DirectoryOutput {
-    pub fn new(path: PathBuf, pretty: bool) -> Self {
-        Self { pretty, path }
+    /// Folds the case of filenames when checking for collisions only if the filesystem
+    /// holding `path` does
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            format: JsonFormat::default(),
+            compressor: None,
+            ledger: Ledger::new(is_case_insensitive(&path)),
+            path,
+        }
     }
 
-    fn write_file(&self, filename: &str, content: Value) -> std::io::Result<()> {
-        let mut path = self.path.clone();
-        path.push(filename);
+    fn write_file(&self, filename: &Path, content: &[u8]) -> std::io::Result<()> {
+        let path = self.path.join(filename);
+        if let Some(parent) = path.parent() {
+            create_dir_all(parent)?;
+        }
         log::info!("Writing file {}", path.display());
         let file = OpenOptions::new()
             .write(true)
             .create(true)
             .truncate(true)
             .open(&path)?;
-        let mut writer = BufWriter::new(file);
-        match self.pretty {
-            true => serde_json::to_writer_pretty(&mut writer, &content)?,
-            false => serde_json::to_writer(&mut writer, &content)?,
-        }
-        Ok(())
+        let mut writer = BufWriter::new(Encoder::new(file, self.compressor)?);
+        writer.write_all(content)?;
+        writer.flush()?;
+        writer.get_mut().try_finish()
     }
 }
 
 impl Writeable for DirectoryOutput {
-    fn set_pretty(&mut self, pretty: bool) {
-        self.pretty = pretty;
+    fn set_format(&mut self, format: JsonFormat) {
+        self.format = format;
+    }
+
+    /// Compresses each file individually, adding the compression extension to its name
+    fn set_compression(&mut self, compressor: Compressor) -> std::io::Result<()> {
+        self.compressor = Some(compressor);
+        Ok(())
+    }
+
+    fn set_collision_policy(&mut self, policy: CollisionPolicy) {
+        self.ledger.set_collision_policy(policy);
     }
 
-    fn write_entries(&self, mut entries: Vec<(String, Value)>) -> std::io::Result<()> {
-        if self.path != PathBuf::from(".") {
+    fn collisions(&self) -> usize {
+        self.ledger.collisions()
+    }
+
+    fn set_manifest(&mut self, manifest: bool) {
+        self.ledger.set_manifest(manifest);
+    }
+
+    fn write_entries(&self, entries: Vec<(String, Value)>) -> std::io::Result<()> {
+        self.write_keyed_entries(keyed(entries))
+    }
+
+    fn write_keyed_entries(&self, entries: Vec<KeyedEntry>) -> std::io::Result<()> {
+        if self.path.as_path() != Path::new(".") {
             //log::info!("Creating directory {}", self.path.display());
             create_dir_all(&self.path)?;
         }
 
-        entries.par_drain(..).for_each(|(key, value)| {
-            self.write_file(&format!("{key}.json"), value)
-                .err()
-                .map(|e| {
-                    log::error!("Error writing to file: {e}");
-                });
-        });
-        Ok(())
+        let entries = self.ledger.claim(entries)?;
+
+        let files = entries
+            .into_par_iter()
+            .map(|(keys, name, value)| {
+                let filename = match self.compressor {
+                    Some(compressor) => {
+                        format!("{name}.json.{}", compressor.compression.extension())
+                    }
+                    None => format!("{name}.json"),
+                };
+                let content = self.format.to_document(&value)?.into_bytes();
+                Ok((keys, filename, content))
+            })
+            .collect::<std::io::Result<Vec<_>>>()?;
+        for (keys, filename, content) in &files {
+            self.ledger.record(keys.clone(), filename.clone(), content);
+        }
+        files
+            .par_iter()
+            .try_for_each(|(_, filename, content)| self.write_file(Path::new(filename), content))
+    }
+
+    fn finish(&self) -> std::io::Result<()> {
+        let Some(manifest) = self.ledger.manifest()? else {
+            return Ok(());
+        };
+        create_dir_all(&self.path)?;
+        let path = self.path.join(MANIFEST_FILE);
+        log::info!("Writing manifest {}", path.display());
+        std::fs::write(path, manifest)
     }
 }
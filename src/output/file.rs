@@ -1,4 +1,8 @@
-use super::{Appendable, Writeable};
+use super::{Appendable, JsonFormat, LockedWriter, ObjectWriter, Writeable};
+use crate::{
+    compression::{Compressor, Encoder},
+    filename,
+};
 use serde_json::Value;
 use std::{
     fs::{File, OpenOptions},
@@ -7,52 +11,116 @@ use std::{
     sync::{Arc, Mutex},
 };
 
-#[derive(Clone, Debug)]
+#[derive(Clone)]
 pub struct FileOutput {
-    pretty: bool,
-    writer: Arc<Mutex<BufWriter<File>>>,
+    format: JsonFormat,
+    writer: Arc<Mutex<LazyFile>>,
+}
+
+/// The output file, created on the first write so it is only ever wrapped in one encoder,
+/// after `--compress` has had its say
+struct LazyFile {
+    path: PathBuf,
+    compressor: Option<Compressor>,
+    writer: Option<BufWriter<Encoder<File>>>,
+}
+
+impl LazyFile {
+    fn writer(&mut self) -> std::io::Result<&mut BufWriter<Encoder<File>>> {
+        if self.writer.is_none() {
+            let writer = Encoder::new(open_file(&self.path)?, self.compressor)?;
+            self.writer = Some(BufWriter::new(writer));
+        }
+        Ok(self.writer.as_mut().expect("Output file was just opened"))
+    }
+}
+
+impl Write for LazyFile {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.writer()?.write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match &mut self.writer {
+            Some(writer) => writer.flush(),
+            None => Ok(()),
+        }
+    }
 }
 
 impl Appendable for FileOutput {
     fn append(&self, content: Value) -> std::io::Result<()> {
         let mut guard = self.writer.lock().expect("Failed to get writer lock");
-        match self.pretty {
-            true => serde_json::to_writer_pretty(&mut *guard, &content)?,
-            false => serde_json::to_writer(&mut *guard, &content)?,
-        }
-        writeln!(&mut *guard)?;
-        Ok(())
+        writeln!(&mut *guard, "{}", self.format.to_string(&content)?)
+    }
+
+    fn object_writer(&self) -> std::io::Result<ObjectWriter<'_>> {
+        let guard = self.writer.lock().expect("Failed to get writer lock");
+        Ok(ObjectWriter::new(
+            Box::new(LockedWriter(guard)),
+            self.format.clone(),
+        ))
+    }
+
+    fn array_writer(&self) -> std::io::Result<ObjectWriter<'_>> {
+        let guard = self.writer.lock().expect("Failed to get writer lock");
+        Ok(ObjectWriter::array(
+            Box::new(LockedWriter(guard)),
+            self.format.clone(),
+        ))
     }
 }
 
 impl Writeable for FileOutput {
-    fn set_pretty(&mut self, pretty: bool) {
-        self.pretty = pretty;
+    fn set_format(&mut self, format: JsonFormat) {
+        self.format = format;
+    }
+
+    fn set_compression(&mut self, compressor: Compressor) -> std::io::Result<()> {
+        let mut guard = self.writer.lock().expect("Failed to get writer lock");
+        log::info!("Compressing {} with {:?}", guard.path.display(), compressor);
+        guard.compressor = Some(compressor);
+        Ok(())
     }
 
     fn write_entries(&self, entries: Vec<(String, Value)>) -> std::io::Result<()> {
         let mut guard = self.writer.lock().expect("Failed to get writer lock");
         for (key, value) in entries {
-            let entry = serde_json::json!({key: value});
-            match self.pretty {
-                true => serde_json::to_writer_pretty(&mut *guard, &entry)?,
-                false => serde_json::to_writer(&mut *guard, &entry)?,
-            }
-            writeln!(&mut *guard)?;
+            // Keys are encoded as filenames, but printed as they were in the input
+            let entry = serde_json::json!({filename::decode(&key): value});
+            writeln!(&mut *guard, "{}", self.format.to_string(&entry)?)?;
         }
         Ok(())
     }
+
+    /// Creates the file even when nothing was written to it
+    fn finish(&self) -> std::io::Result<()> {
+        let mut guard = self.writer.lock().expect("Failed to get writer lock");
+        let writer = guard.writer()?;
+        writer.flush()?;
+        writer.get_mut().try_finish()
+    }
 }
 
 impl FileOutput {
-    pub fn new(path: PathBuf, pretty: bool) -> Self {
-        let file = OpenOptions::new()
-            .write(true)
-            .create(true)
-            .truncate(true)
-            .open(&path)
-            .expect("Failed to open file");
-        let writer = Arc::new(Mutex::new(BufWriter::new(file)));
-        Self { pretty, writer }
+    /// Writes to the file at `path`, compressing it when the extension is `.gz`, `.zst` or `.bz2`
+    pub fn new(path: PathBuf) -> Self {
+        let compressor = Compressor::from_extension(&path);
+        Self {
+            format: JsonFormat::default(),
+            writer: Arc::new(Mutex::new(LazyFile {
+                path,
+                compressor,
+                writer: None,
+            })),
+        }
     }
 }
+
+fn open_file(path: &PathBuf) -> std::io::Result<File> {
+    OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(path)
+}
@@ -0,0 +1,508 @@
+use crate::processor::KeyOrder;
+use eyre::{Report, Result, eyre};
+use serde_json::{Number, Value};
+use std::{
+    cmp::Ordering,
+    io::{Error, ErrorKind},
+};
+
+/// Whitespace written for each level of nesting in pretty-printed output
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Indent {
+    Spaces(usize),
+    Tab,
+}
+
+impl Default for Indent {
+    fn default() -> Self {
+        Self::Spaces(2)
+    }
+}
+
+impl Indent {
+    fn as_string(self) -> String {
+        match self {
+            Self::Spaces(width) => " ".repeat(width),
+            Self::Tab => "\t".to_string(),
+        }
+    }
+}
+
+impl std::str::FromStr for Indent {
+    type Err = Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "tab" => Ok(Self::Tab),
+            s => s
+                .parse()
+                .map(Self::Spaces)
+                .map_err(|_| eyre!("Unknown indent `{s}`, expected a number of spaces or tab")),
+        }
+    }
+}
+
+/// How every writer formats JSON, built once with a `JsonFormatBuilder`.
+///
+/// Pretty-printed objects and arrays that fit within `inline_width` columns are kept on
+/// a single line. The trailing newline applies to outputs writing one file per entry,
+/// outputs holding several documents always end each one with a newline.
+///
+/// The canonical format is RFC 8785 (JCS): compact, keys sorted by their UTF-16 code units,
+/// numbers written like ECMAScript does and only the escapes JSON requires. It overrides
+/// every other option, so each file is exactly the canonical bytes of its value.
+#[derive(Clone, Debug, PartialEq)]
+pub struct JsonFormat {
+    canonical: bool,
+    pretty: bool,
+    indent: String,
+    inline_width: usize,
+    ensure_ascii: bool,
+    trailing_newline: bool,
+    key_order: KeyOrder,
+}
+
+impl Default for JsonFormat {
+    fn default() -> Self {
+        JsonFormatBuilder::new().build()
+    }
+}
+
+impl JsonFormat {
+    /// The RFC 8785 canonical format, for output that has to be byte-stable
+    pub fn canonical() -> Self {
+        JsonFormatBuilder::new().canonical(true).build()
+    }
+
+    pub fn is_pretty(&self) -> bool {
+        self.pretty
+    }
+
+    pub fn indent(&self) -> &str {
+        &self.indent
+    }
+
+    /// Whether object keys are written in a different order than they were read
+    pub fn reorders_keys(&self) -> bool {
+        self.canonical || !self.key_order.is_preserve()
+    }
+
+    pub fn compare_keys(&self, a: &str, b: &str) -> Ordering {
+        match self.canonical {
+            true => a.encode_utf16().cmp(b.encode_utf16()),
+            false => self.key_order.compare(a, b),
+        }
+    }
+
+    /// Orders a list of top-level entries by their key
+    pub fn sort_entries(&self, entries: &mut [(String, Value)]) {
+        if self.reorders_keys() {
+            entries.sort_by(|(a, _), (b, _)| self.compare_keys(a, b));
+        }
+    }
+
+    /// Renders a whole document, ending with a newline when `trailing_newline` is set
+    pub fn to_document(&self, value: &Value) -> std::io::Result<String> {
+        let mut text = self.to_string(value)?;
+        if self.trailing_newline {
+            text.push('\n');
+        }
+        Ok(text)
+    }
+
+    /// Renders `value`, failing only when the canonical format can't represent a number
+    pub fn to_string(&self, value: &Value) -> std::io::Result<String> {
+        self.to_string_at(value, 0, 0)
+    }
+
+    /// Renders `value` as if it starts at `column` of a line nested `level` times
+    pub fn to_string_at(
+        &self,
+        value: &Value,
+        level: usize,
+        column: usize,
+    ) -> std::io::Result<String> {
+        let mut text = String::new();
+        self.render(value, level, column, !self.pretty, &mut text)?;
+        Ok(self.escape(text))
+    }
+
+    /// Renders an object key, without the separator that follows it
+    pub fn key_to_string(&self, key: &str) -> String {
+        self.escape(Value::from(key).to_string())
+    }
+
+    fn render(
+        &self,
+        value: &Value,
+        level: usize,
+        column: usize,
+        inline: bool,
+        text: &mut String,
+    ) -> std::io::Result<()> {
+        let (open, close, members) = match value {
+            Value::Object(object) if !object.is_empty() => {
+                let mut members: Vec<(Option<&str>, &Value)> = object
+                    .iter()
+                    .map(|(key, value)| (Some(key.as_str()), value))
+                    .collect();
+                if self.reorders_keys() {
+                    members.sort_by(|(a, _), (b, _)| {
+                        self.compare_keys(a.unwrap_or(""), b.unwrap_or(""))
+                    });
+                }
+                ('{', '}', members)
+            }
+            Value::Array(array) if !array.is_empty() => {
+                ('[', ']', array.iter().map(|value| (None, value)).collect())
+            }
+            Value::Number(number) if self.canonical => {
+                text.push_str(&ecmascript_number(number)?);
+                return Ok(());
+            }
+            scalar => {
+                text.push_str(&scalar.to_string());
+                return Ok(());
+            }
+        };
+
+        let inline = inline || self.fits(value, column)?;
+        let (comma, colon) = match (self.pretty, inline) {
+            (false, _) => (",", ":"),
+            (true, true) => (", ", ": "),
+            (true, false) => (",", ": "),
+        };
+        text.push(open);
+        for (i, (key, member)) in members.into_iter().enumerate() {
+            if i > 0 {
+                text.push_str(comma);
+            }
+            let mut column = column;
+            if !inline {
+                text.push('\n');
+                text.push_str(&self.indent.repeat(level + 1));
+                column = self.indent.len() * (level + 1);
+            }
+            if let Some(key) = key {
+                let key = Value::from(key).to_string();
+                column += self.width(&key) + colon.len();
+                text.push_str(&key);
+                text.push_str(colon);
+            }
+            self.render(member, level + 1, column, inline, text)?;
+        }
+        if !inline {
+            text.push('\n');
+            text.push_str(&self.indent.repeat(level));
+        }
+        text.push(close);
+        Ok(())
+    }
+
+    /// Whether `value` fits on the rest of a line starting at `column`
+    fn fits(&self, value: &Value, column: usize) -> std::io::Result<bool> {
+        if self.inline_width == 0 {
+            return Ok(false);
+        }
+        let mut text = String::new();
+        self.render(value, 0, 0, true, &mut text)?;
+        Ok(column + self.width(&text) <= self.inline_width)
+    }
+
+    /// Number of characters `text` takes once written, counting each `\uXXXX` escape
+    /// that `ensure_ascii` adds in its place
+    pub fn width(&self, text: &str) -> usize {
+        match self.ensure_ascii {
+            true => text
+                .chars()
+                .map(|c| match c.is_ascii() {
+                    true => 1,
+                    false => 6 * c.len_utf16(),
+                })
+                .sum(),
+            false => text.chars().count(),
+        }
+    }
+
+    /// Escapes every non-ASCII character when `ensure_ascii` is set, which can only
+    /// appear inside strings
+    fn escape(&self, text: String) -> String {
+        if !self.ensure_ascii || text.is_ascii() {
+            return text;
+        }
+        let mut escaped = String::with_capacity(text.len());
+        for c in text.chars() {
+            if c.is_ascii() {
+                escaped.push(c);
+                continue;
+            }
+            let mut units = [0; 2];
+            for unit in c.encode_utf16(&mut units) {
+                escaped.push_str(&format!("\\u{unit:04x}"));
+            }
+        }
+        escaped
+    }
+}
+
+/// Writes a number like ECMAScript's `Number.prototype.toString`, as RFC 8785 requires.
+///
+/// Numbers beyond the range of a double, like `1e400`, have no canonical form and fail.
+fn ecmascript_number(number: &Number) -> std::io::Result<String> {
+    let value = number
+        .as_f64()
+        .filter(|value| value.is_finite())
+        .ok_or_else(|| {
+            Error::new(
+                ErrorKind::InvalidData,
+                format!("{number} is out of range for canonical JSON, which only has doubles"),
+            )
+        })?;
+    if value == 0.0 {
+        return Ok("0".to_string());
+    }
+    // Rust writes the shortest digits that round-trip, like ECMAScript does
+    let scientific = format!("{:e}", value.abs());
+    let (mantissa, exponent) = scientific
+        .split_once('e')
+        .expect("Scientific notation has an exponent");
+    let digits = mantissa.replace('.', "");
+    let k = digits.len() as i32;
+    // The value is 0.digits × 10^n
+    let n = exponent.parse::<i32>().expect("Exponent is a number") + 1;
+    let text = if k <= n && n <= 21 {
+        digits + &"0".repeat((n - k) as usize)
+    } else if 0 < n && n <= 21 {
+        let (whole, fraction) = digits.split_at(n as usize);
+        format!("{whole}.{fraction}")
+    } else if -6 < n && n <= 0 {
+        format!("0.{}{digits}", "0".repeat(-n as usize))
+    } else {
+        let sign = if n > 0 { "+" } else { "-" };
+        format!("{mantissa}e{sign}{}", (n - 1).abs())
+    };
+    Ok(match value < 0.0 {
+        true => format!("-{text}"),
+        false => text,
+    })
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct JsonFormatBuilder {
+    canonical: bool,
+    pretty: bool,
+    indent: Indent,
+    inline_width: usize,
+    ensure_ascii: bool,
+    no_trailing_newline: bool,
+    key_order: KeyOrder,
+}
+
+impl JsonFormatBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Writes RFC 8785 canonical JSON, ignoring every other option
+    pub fn canonical(mut self, canonical: bool) -> Self {
+        self.canonical = canonical;
+        self
+    }
+
+    /// Writes each member on its own line, instead of compact single-line JSON
+    pub fn pretty(mut self, pretty: bool) -> Self {
+        self.pretty = pretty;
+        self
+    }
+
+    pub fn indent(mut self, indent: Indent) -> Self {
+        self.indent = indent;
+        self
+    }
+
+    /// Keeps pretty-printed objects and arrays on one line when they fit in this many
+    /// columns, `0` never does
+    pub fn inline_width(mut self, width: usize) -> Self {
+        self.inline_width = width;
+        self
+    }
+
+    /// Escapes non-ASCII characters as `\uXXXX`
+    pub fn ensure_ascii(mut self, ensure_ascii: bool) -> Self {
+        self.ensure_ascii = ensure_ascii;
+        self
+    }
+
+    /// Ends each file with a newline, which is the default
+    pub fn trailing_newline(mut self, trailing_newline: bool) -> Self {
+        self.no_trailing_newline = !trailing_newline;
+        self
+    }
+
+    pub fn key_order(mut self, key_order: KeyOrder) -> Self {
+        self.key_order = key_order;
+        self
+    }
+
+    pub fn build(self) -> JsonFormat {
+        let canonical = self.canonical;
+        JsonFormat {
+            canonical,
+            pretty: self.pretty && !canonical,
+            indent: self.indent.as_string(),
+            inline_width: self.inline_width,
+            ensure_ascii: self.ensure_ascii && !canonical,
+            trailing_newline: !self.no_trailing_newline && !canonical,
+            key_order: self.key_order,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::processor::KeySort;
+    use serde_json::json;
+
+    fn pretty() -> JsonFormatBuilder {
+        JsonFormatBuilder::new().pretty(true)
+    }
+
+    #[test]
+    fn matches_serde() {
+        let value = json!({"alpha": {"list": [1, 2], "empty": {}}, "bravo": "multi\nline"});
+        assert_eq!(
+            pretty().build().to_string(&value).unwrap(),
+            serde_json::to_string_pretty(&value).unwrap()
+        );
+        assert_eq!(
+            JsonFormat::default().to_string(&value).unwrap(),
+            serde_json::to_string(&value).unwrap()
+        );
+    }
+
+    #[test]
+    fn indents_with_tabs() {
+        let format = pretty().indent("tab".parse().unwrap()).build();
+        assert_eq!(
+            format.to_string(&json!({"a": [1]})).unwrap(),
+            "{\n\t\"a\": [\n\t\t1\n\t]\n}"
+        );
+    }
+
+    #[test]
+    fn inlines_short_values() {
+        let format = pretty().inline_width(20).build();
+        // Keys in alphabetical order, so the output is the same with or without `preserve_order`
+        let value = json!({"long": ["alpha", "bravo", "charlie"], "short": [1, 2]});
+        assert_eq!(
+            format.to_string(&value).unwrap(),
+            "{\n  \"long\": [\n    \"alpha\",\n    \"bravo\",\n    \"charlie\"\n  ],\n  \"short\": [1, 2]\n}"
+        );
+        assert_eq!(format.to_string(&json!({"a": 1})).unwrap(), "{\"a\": 1}");
+    }
+
+    #[test]
+    fn escapes_non_ascii() {
+        let format = JsonFormatBuilder::new().ensure_ascii(true).build();
+        assert_eq!(
+            format.to_string(&json!({"café": "😀"})).unwrap(),
+            r#"{"caf\u00e9":"\ud83d\ude00"}"#
+        );
+    }
+
+    #[test]
+    fn orders_keys_while_rendering() {
+        let format = JsonFormatBuilder::new()
+            .key_order(KeyOrder::new(KeySort::Alpha, Some(vec!["id".to_string()])))
+            .build();
+        let value: Value = serde_json::from_str(r#"{"b": {"z": 1, "id": 2}, "a": 3}"#).unwrap();
+        assert_eq!(
+            format.to_string(&value).unwrap(),
+            r#"{"a":3,"b":{"id":2,"z":1}}"#
+        );
+    }
+
+    #[test]
+    fn ends_documents_with_newline() {
+        assert_eq!(JsonFormat::default().to_document(&json!(1)).unwrap(), "1\n");
+        let format = JsonFormatBuilder::new().trailing_newline(false).build();
+        assert_eq!(format.to_document(&json!(1)).unwrap(), "1");
+    }
+
+    #[test]
+    fn canonicalizes_rfc_8785_example() {
+        let value: Value = serde_json::from_str(
+            r#"{"numbers": [333333333.33333329, 1E30, 4.50, 2e-3, 0.000000000000000000000000001],
+                "string": "\u20ac$\u000F\u000aA'\u0042\u0022\u005c\\\"\/",
+                "literals": [null, true, false]}"#,
+        )
+        .unwrap();
+        let format = JsonFormatBuilder::new()
+            .canonical(true)
+            .pretty(true)
+            .ensure_ascii(true)
+            .build();
+        assert_eq!(
+            format.to_document(&value).unwrap(),
+            r#"{"literals":[null,true,false],"numbers":[333333333.3333333,1e+30,4.5,0.002,1e-27],"string":"€$\u000f\nA'B\"\\\\\"/"}"#
+        );
+    }
+
+    #[test]
+    fn sorts_canonical_keys_by_utf16() {
+        let value: Value = serde_json::from_str(
+            r#"{"\u20ac": 1, "\r": 2, "\ufb33": 3, "1": 4, "\ud83d\ude00": 5, "\u0080": 6, "\u00f6": 7}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            JsonFormat::canonical().to_string(&value).unwrap(),
+            "{\"\\r\":2,\"1\":4,\"\u{80}\":6,\"ö\":7,\"€\":1,\"😀\":5,\"\u{fb33}\":3}"
+        );
+    }
+
+    #[test]
+    fn writes_ecmascript_numbers() {
+        let number = |text: &str| ecmascript_number(&serde_json::from_str(text).unwrap()).unwrap();
+        assert_eq!(number("-0.0"), "0");
+        assert_eq!(number("100"), "100");
+        assert_eq!(number("1e21"), "1e+21");
+        assert_eq!(number("1e20"), "100000000000000000000");
+        assert_eq!(number("-1.5e-7"), "-1.5e-7");
+        assert_eq!(number("0.000001"), "0.000001");
+        assert_eq!(number("123.456"), "123.456");
+    }
+
+    /// Only arbitrary precision keeps numbers that don't fit a double, serde rejects them otherwise
+    #[cfg(feature = "arbitrary_precision")]
+    #[test]
+    fn rejects_numbers_beyond_doubles() {
+        let value: Value = serde_json::from_str(r#"{"big": 1e400}"#).unwrap();
+        assert!(JsonFormat::canonical().to_string(&value).is_err());
+        assert_eq!(
+            JsonFormat::default().to_string(&value).unwrap(),
+            r#"{"big":1e400}"#
+        );
+    }
+
+    #[test]
+    fn measures_width_after_escaping() {
+        // Each `é` takes six columns once escaped, so the array no longer fits
+        let value = json!({"k": ["éééé"]});
+        let format = pretty().inline_width(16);
+        assert_eq!(
+            format.clone().build().to_string(&value).unwrap(),
+            "{\"k\": [\"éééé\"]}"
+        );
+        assert_eq!(
+            format.ensure_ascii(true).build().to_string(&value).unwrap(),
+            "{\n  \"k\": [\n    \"\\u00e9\\u00e9\\u00e9\\u00e9\"\n  ]\n}"
+        );
+        // Keys count characters rather than bytes
+        let value = json!({"a": 1, "éééé": [1, 2, 3]});
+        assert_eq!(
+            pretty().inline_width(20).build().to_string(&value).unwrap(),
+            "{\n  \"a\": 1,\n  \"éééé\": [1, 2, 3]\n}"
+        );
+    }
+}
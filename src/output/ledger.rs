@@ -0,0 +1,97 @@
+use crate::{
+    filename::{Claimed, CollisionPolicy, decode, relative_path},
+    manifest::{MANIFEST_FILE, Manifest},
+};
+use serde_json::Value;
+use std::sync::{Arc, Mutex};
+
+/// An entry to write: the keys it came from, its name relative to the output, and its value
+pub type KeyedEntry = (Vec<String>, String, Value);
+
+/// Keeps track of what a directory or archive output has written: the names taken so far,
+/// so entries can't overwrite each other, and the manifest listing them when one was asked for
+#[derive(Clone, Debug, Default)]
+pub struct Ledger {
+    claimed: Arc<Mutex<Claimed>>,
+    manifest: Option<Arc<Mutex<Manifest>>>,
+}
+
+impl Ledger {
+    /// Folds the case of names when checking for collisions if `fold_case` is set
+    pub fn new(fold_case: bool) -> Self {
+        let mut claimed = Claimed::default();
+        claimed.fold_case = fold_case;
+        Self {
+            claimed: Arc::new(Mutex::new(claimed)),
+            manifest: None,
+        }
+    }
+
+    fn claimed(&self) -> std::sync::MutexGuard<'_, Claimed> {
+        self.claimed
+            .lock()
+            .expect("Failed to get claimed names lock")
+    }
+
+    pub fn set_collision_policy(&self, policy: CollisionPolicy) {
+        self.claimed().policy = policy;
+    }
+
+    pub fn collisions(&self) -> usize {
+        self.claimed().collisions
+    }
+
+    /// Starts a manifest, reserving its name so no entry can overwrite it
+    pub fn set_manifest(&mut self, manifest: bool) {
+        if !manifest || self.manifest.is_some() {
+            return;
+        }
+        let name = MANIFEST_FILE.trim_end_matches(".json").to_string();
+        self.claimed()
+            .claim_all(vec![(name, Value::Null)])
+            .expect("Manifest name was already claimed");
+        self.manifest = Some(Arc::new(Mutex::new(Manifest::default())));
+    }
+
+    /// Checks every name before any entry is written, returning the entries to write under
+    /// the names they were given by the collision policy
+    pub fn claim(&self, entries: Vec<KeyedEntry>) -> std::io::Result<Vec<KeyedEntry>> {
+        let entries = entries
+            .into_iter()
+            .map(|(keys, name, value)| relative_path(&name).map(|_| (name, (keys, value))))
+            .collect::<std::io::Result<Vec<_>>>()?;
+        Ok(self
+            .claimed()
+            .claim_all(entries)?
+            .into_iter()
+            .map(|(name, (keys, value))| (keys, name, value))
+            .collect())
+    }
+
+    /// Lists an entry in the manifest, if there is one
+    pub fn record(&self, keys: Vec<String>, path: String, content: &[u8]) {
+        if let Some(manifest) = &self.manifest {
+            manifest
+                .lock()
+                .expect("Failed to get manifest lock")
+                .push(keys, path, content);
+        }
+    }
+
+    /// The manifest to write after the last entry, if one was asked for
+    pub fn manifest(&self) -> std::io::Result<Option<Vec<u8>>> {
+        let Some(manifest) = &self.manifest else {
+            return Ok(None);
+        };
+        let manifest = manifest.lock().expect("Failed to get manifest lock");
+        Ok(Some(manifest.to_vec()?))
+    }
+}
+
+/// Keys entries by the `/`-separated parts of their names, for entries named after their keys
+pub fn keyed(entries: Vec<(String, Value)>) -> Vec<KeyedEntry> {
+    entries
+        .into_iter()
+        .map(|(name, value)| (name.split('/').map(decode).collect(), name, value))
+        .collect()
+}
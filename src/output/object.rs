@@ -0,0 +1,185 @@
+use super::JsonFormat;
+use serde_json::Value;
+use std::io::{Result, Write};
+
+/// Writes a single JSON object or array one entry at a time.
+///
+/// The opening bracket is written on the first entry and the closing bracket by `finish`,
+/// so only the entry currently being written has to be held in memory. When object keys
+/// have to be reordered, entries are held until `finish` instead.
+pub struct ObjectWriter<'a> {
+    writer: Box<dyn Write + 'a>,
+    format: JsonFormat,
+    count: usize,
+    brackets: (&'static str, &'static str),
+    /// Entries waiting to be reordered by `finish`
+    pending: Vec<(String, Value)>,
+}
+
+impl<'a> ObjectWriter<'a> {
+    pub fn new(writer: Box<dyn Write + 'a>, format: JsonFormat) -> Self {
+        Self {
+            writer,
+            format,
+            count: 0,
+            brackets: ("{", "}"),
+            pending: Vec::new(),
+        }
+    }
+
+    pub fn array(writer: Box<dyn Write + 'a>, format: JsonFormat) -> Self {
+        Self {
+            brackets: ("[", "]"),
+            ..Self::new(writer, format)
+        }
+    }
+
+    /// Writes a `"key": value` member, only valid for objects
+    pub fn write_entry(&mut self, key: &str, value: &Value) -> Result<()> {
+        if self.format.reorders_keys() {
+            self.pending.push((key.to_string(), value.clone()));
+            return Ok(());
+        }
+        self.write_key(key, value)
+    }
+
+    /// Writes a bare value, only valid for arrays
+    pub fn write_element(&mut self, value: &Value) -> Result<()> {
+        self.write_separator()?;
+        let column = self.format.indent().len();
+        self.write_value(value, column)
+    }
+
+    /// Closes the object or array and flushes the underlying writer
+    pub fn finish(mut self) -> Result<()> {
+        let mut pending = std::mem::take(&mut self.pending);
+        self.format.sort_entries(&mut pending);
+        for (key, value) in pending {
+            self.write_key(&key, &value)?;
+        }
+        let (open, close) = self.brackets;
+        match (self.count, self.format.is_pretty()) {
+            (0, _) => write!(self.writer, "{open}{close}")?,
+            (_, true) => write!(self.writer, "\n{close}")?,
+            (_, false) => write!(self.writer, "{close}")?,
+        }
+        writeln!(self.writer)?;
+        self.writer.flush()
+    }
+
+    fn write_key(&mut self, key: &str, value: &Value) -> Result<()> {
+        self.write_separator()?;
+        let key = self.format.key_to_string(key);
+        let colon = match self.format.is_pretty() {
+            true => ": ",
+            false => ":",
+        };
+        write!(self.writer, "{key}{colon}")?;
+        let column = self.format.indent().len() + key.chars().count() + colon.len();
+        self.write_value(value, column)
+    }
+
+    fn write_separator(&mut self) -> Result<()> {
+        let (open, _) = self.brackets;
+        let indent = self.format.indent();
+        match (self.count, self.format.is_pretty()) {
+            (0, true) => write!(self.writer, "{open}\n{indent}"),
+            (0, false) => write!(self.writer, "{open}"),
+            (_, true) => write!(self.writer, ",\n{indent}"),
+            (_, false) => write!(self.writer, ","),
+        }
+    }
+
+    fn write_value(&mut self, value: &Value, column: usize) -> Result<()> {
+        let value = self.format.to_string_at(value, 1, column)?;
+        self.writer.write_all(value.as_bytes())?;
+        self.count += 1;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        output::JsonFormatBuilder,
+        processor::{KeyOrder, KeySort},
+    };
+    use serde_json::json;
+
+    fn write_object(entries: &[(&str, Value)], pretty: bool) -> String {
+        let mut buf = Vec::new();
+        let format = JsonFormatBuilder::new().pretty(pretty).build();
+        let mut writer = ObjectWriter::new(Box::new(&mut buf), format);
+        for (key, value) in entries {
+            writer.write_entry(key, value).unwrap();
+        }
+        writer.finish().unwrap();
+        String::from_utf8(buf).unwrap()
+    }
+
+    #[test]
+    fn matches_serde_pretty() {
+        let entries = [
+            (
+                "alpha",
+                json!({"letter": {"lowercase": "a"}, "list": [1, 2]}),
+            ),
+            ("bravo", json!("multi\nline")),
+        ];
+        let expected = json!({
+            "alpha": {"letter": {"lowercase": "a"}, "list": [1, 2]},
+            "bravo": "multi\nline",
+        });
+        assert_eq!(
+            write_object(&entries, true),
+            serde_json::to_string_pretty(&expected).unwrap() + "\n"
+        );
+    }
+
+    #[test]
+    fn matches_serde_compact() {
+        let entries = [("alpha", json!(1)), ("bravo", json!([true, null]))];
+        let expected = json!({"alpha": 1, "bravo": [true, null]});
+        assert_eq!(write_object(&entries, false), expected.to_string() + "\n");
+    }
+
+    #[test]
+    fn reorders_keys() {
+        let mut buf = Vec::new();
+        let order = KeyOrder::new(KeySort::Alpha, Some(vec!["id".to_string()]));
+        let format = JsonFormatBuilder::new().key_order(order).build();
+        let mut writer = ObjectWriter::new(Box::new(&mut buf), format);
+        writer
+            .write_entry("bravo", &json!({"b": 1, "a": 2}))
+            .unwrap();
+        writer.write_entry("alpha", &json!(3)).unwrap();
+        writer.write_entry("id", &json!(4)).unwrap();
+        writer.finish().unwrap();
+        assert_eq!(
+            String::from_utf8(buf).unwrap(),
+            "{\"id\":4,\"alpha\":3,\"bravo\":{\"a\":2,\"b\":1}}\n"
+        );
+    }
+
+    #[test]
+    fn empty_object() {
+        assert_eq!(write_object(&[], true), "{}\n");
+    }
+
+    #[test]
+    fn array_matches_serde_pretty() {
+        let values = [json!({"alpha": [1]}), json!(2)];
+        let mut buf = Vec::new();
+        let format = JsonFormatBuilder::new().pretty(true).build();
+        let mut writer = ObjectWriter::array(Box::new(&mut buf), format);
+        for value in &values {
+            writer.write_element(value).unwrap();
+        }
+        writer.finish().unwrap();
+        assert_eq!(
+            String::from_utf8(buf).unwrap(),
+            serde_json::to_string_pretty(&json!(values)).unwrap() + "\n"
+        );
+    }
+}
@@ -1,43 +1,81 @@
-use super::{Appendable, Writeable};
+use super::{Appendable, JsonFormat, LockedWriter, ObjectWriter, Writeable};
+use crate::{
+    compression::{Compressor, Encoder},
+    filename,
+};
 use serde_json::Value;
-use std::io::stdout;
+use std::{
+    io::{Stdout, Write, stdout},
+    sync::{Arc, Mutex},
+};
 
-#[derive(Clone, Debug)]
+#[derive(Clone)]
 pub struct StreamOutput {
-    pretty: bool,
+    format: JsonFormat,
+    writer: Arc<Mutex<Encoder<Stdout>>>,
 }
 
 impl StreamOutput {
-    pub fn new(pretty: bool) -> Self {
-        Self { pretty }
+    pub fn new() -> Self {
+        Self {
+            format: JsonFormat::default(),
+            writer: Arc::new(Mutex::new(Encoder::Plain(stdout()))),
+        }
     }
 }
 
 impl Appendable for StreamOutput {
     fn append(&self, content: Value) -> std::io::Result<()> {
-        match self.pretty {
-            true => serde_json::to_writer_pretty(stdout(), &content)?,
-            false => serde_json::to_writer(stdout(), &content)?,
-        }
-        println!();
-        Ok(())
+        let mut guard = self.writer.lock().expect("Failed to get writer lock");
+        writeln!(&mut *guard, "{}", self.format.to_string(&content)?)
+    }
+
+    fn object_writer(&self) -> std::io::Result<ObjectWriter<'_>> {
+        let guard = self.writer.lock().expect("Failed to get writer lock");
+        Ok(ObjectWriter::new(
+            Box::new(LockedWriter(guard)),
+            self.format.clone(),
+        ))
+    }
+
+    fn array_writer(&self) -> std::io::Result<ObjectWriter<'_>> {
+        let guard = self.writer.lock().expect("Failed to get writer lock");
+        Ok(ObjectWriter::array(
+            Box::new(LockedWriter(guard)),
+            self.format.clone(),
+        ))
     }
 }
 
 impl Writeable for StreamOutput {
-    fn set_pretty(&mut self, pretty: bool) {
-        self.pretty = pretty;
+    fn set_format(&mut self, format: JsonFormat) {
+        self.format = format;
+    }
+
+    fn set_compression(&mut self, compressor: Compressor) -> std::io::Result<()> {
+        log::info!("Compressing stdout with {:?}", compressor);
+        *self.writer.lock().expect("Failed to get writer lock") = compressor.encoder(stdout())?;
+        Ok(())
+    }
+
+    fn is_stdout(&self) -> bool {
+        true
     }
 
     fn write_entries(&self, mut entries: Vec<(String, Value)>) -> std::io::Result<()> {
+        let mut guard = self.writer.lock().expect("Failed to get writer lock");
         for (key, value) in entries.drain(..) {
-            let entry = serde_json::json!({key: value});
-            match self.pretty {
-                true => serde_json::to_writer_pretty(stdout(), &entry)?,
-                false => serde_json::to_writer(stdout(), &entry)?,
-            }
-            println!();
+            // Keys are encoded as filenames, but printed as they were in the input
+            let entry = serde_json::json!({filename::decode(&key): value});
+            writeln!(&mut *guard, "{}", self.format.to_string(&entry)?)?;
         }
         Ok(())
     }
+
+    fn finish(&self) -> std::io::Result<()> {
+        self.writer
+            .lock()
+            .expect("Failed to get writer lock")
+            .try_finish()
+    }
 }
@@ -1,13 +1,55 @@
+/// Set fields to constants or to where each record came from
+pub mod assign;
+/// Save and restore the document surrounding a split value
+pub mod envelope;
 /// Process JSON objects
 pub mod json;
 /// Encode and decode nested string-escaped JSON objects
 pub mod json_text;
+/// Order the keys of output objects
+pub mod key_order;
 /// Process newline-delimited lists of JSON objects
 mod ndjson;
+/// Select fields with paths like `objects[*].attributes.**._secret`
+pub mod path;
+/// Rename fields and convert the case of keys
+pub mod rename;
+/// Render output names from record fields
+pub mod template;
+/// Apply the field transforms shared by every command
+pub mod transform;
 
-pub use json::Json;
+pub use assign::{Assignment, ContextSource, Origin};
+pub use envelope::Envelope;
+pub use json::{Json, Pseudonymizer};
+pub use key_order::{KeyOrder, KeySort};
 pub use ndjson::{Bundler, BundlerBuilder, Unbundler, UnbundlerBuilder};
+pub use path::FieldPath;
+pub use rename::{KeyCase, Rename};
+pub use template::NameTemplate;
+pub use transform::Transforms;
 
-pub fn dots_to_slashes(str: &str) -> String {
-    "/".to_string() + &str.split('.').collect::<Vec<&str>>().join("/")
+/// Reads the first field matching `field` as a name, converting numbers and booleans to strings.
+///
+/// Empty strings can't name a file, so they are skipped like missing fields.
+pub fn field_to_name(value: &serde_json::Value, field: &FieldPath) -> Option<String> {
+    field
+        .select(value)
+        .into_iter()
+        .find_map(|value| match value {
+            serde_json::Value::String(string) if !string.is_empty() => Some(string.clone()),
+            serde_json::Value::Number(number) => Some(number.to_string()),
+            serde_json::Value::Bool(bool) => Some(bool.to_string()),
+            _ => None,
+        })
+}
+
+/// Checks `actual` against the JSON text `expected`, and its key order too when the
+/// `preserve_order` feature keeps it
+#[cfg(test)]
+pub(crate) fn assert_json(actual: &serde_json::Value, expected: &str) {
+    let value: serde_json::Value = serde_json::from_str(expected).expect("Invalid expected JSON");
+    assert_eq!(actual, &value);
+    #[cfg(feature = "preserve_order")]
+    assert_eq!(actual.to_string(), expected);
 }
@@ -0,0 +1,207 @@
+use super::{FieldPath, path::make_path};
+use eyre::{Report, Result, eyre};
+use serde_json::Value;
+use std::{
+    sync::OnceLock,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Sets a field to a constant with `--set path=<json>`, or to a value from where the record
+/// came from with `--set-from path=<source>`.
+///
+/// Missing parent objects are created, and an existing key keeps its position.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Assignment {
+    path: FieldPath,
+    tokens: Vec<String>,
+    value: Assigned,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Assigned {
+    Constant(Value),
+    From(ContextSource),
+}
+
+/// What a `--set-from` field is set to
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ContextSource {
+    /// The file the record was read from
+    Filename,
+    /// The 1-based line number of an `unbundle` record, or the 0-based position of the
+    /// record or entry otherwise
+    Index,
+    /// The record's key or name
+    Key,
+    /// When the command started, as an RFC 3339 UTC timestamp
+    Now,
+}
+
+/// Where a record came from, for `--set-from`
+#[derive(Clone, Debug, Default)]
+pub struct Origin<'a> {
+    pub filename: Option<&'a str>,
+    pub index: usize,
+    pub key: Option<&'a str>,
+}
+
+impl Assignment {
+    /// Parses `path=<json>`, like `migrationVersion=3` or `tags=["a","b"]`
+    pub fn constant(s: &str) -> Result<Self> {
+        let (path, json) = split(s, "<json>")?;
+        let value = serde_json::from_str(json).map_err(|e| {
+            eyre!("Invalid JSON in `--set {s}`: {e}. Quote strings, like `{path}='\"text\"'`")
+        })?;
+        Self::new(path, Assigned::Constant(value))
+    }
+
+    /// Parses `path=<source>`, like `_file=filename`
+    pub fn from_context(s: &str) -> Result<Self> {
+        let (path, source) = split(s, "<source>")?;
+        Self::new(path, Assigned::From(source.parse()?))
+    }
+
+    fn new(path: &str, value: Assigned) -> Result<Self> {
+        let path: FieldPath = path.parse()?;
+        Ok(Self {
+            tokens: path.tokens()?,
+            path,
+            value,
+        })
+    }
+
+    pub fn apply(&self, value: &mut Value, origin: &Origin) -> Result<()> {
+        let new = match &self.value {
+            Assigned::Constant(constant) => constant.clone(),
+            Assigned::From(ContextSource::Filename) => origin.filename.into(),
+            Assigned::From(ContextSource::Index) => origin.index.into(),
+            Assigned::From(ContextSource::Key) => origin.key.into(),
+            Assigned::From(ContextSource::Now) => started().into(),
+        };
+        let Some((last, parent)) = self.tokens.split_last() else {
+            return Ok(());
+        };
+        let blocked = |token: &str| {
+            eyre!(
+                "Can't set `{}`, there is no object to hold `{token}`",
+                self.path
+            )
+        };
+        match make_path(value, parent).map_err(blocked)? {
+            Value::Object(object) => {
+                object.insert(last.clone(), new);
+            }
+            Value::Array(array) => match last.parse::<usize>() {
+                Ok(index) if index < array.len() => array[index] = new,
+                Ok(index) if index == array.len() => array.push(new),
+                _ => return Err(blocked(last)),
+            },
+            _ => return Err(blocked(last)),
+        }
+        Ok(())
+    }
+}
+
+fn split<'a>(s: &'a str, value: &str) -> Result<(&'a str, &'a str)> {
+    s.split_once('=')
+        .ok_or_else(|| eyre!("`{s}` should look like `path={value}`"))
+}
+
+impl std::str::FromStr for ContextSource {
+    type Err = Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "filename" => Ok(Self::Filename),
+            "index" => Ok(Self::Index),
+            "key" => Ok(Self::Key),
+            "now" => Ok(Self::Now),
+            s => Err(eyre!(
+                "Unknown source `{s}`, expected filename, index, key or now"
+            )),
+        }
+    }
+}
+
+/// When the command started, so every record gets the same timestamp
+fn started() -> &'static str {
+    static STARTED: OnceLock<String> = OnceLock::new();
+    STARTED.get_or_init(|| {
+        let seconds = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |duration| duration.as_secs());
+        rfc3339(seconds)
+    })
+}
+
+/// Formats seconds since the Unix epoch as an RFC 3339 UTC timestamp
+fn rfc3339(seconds: u64) -> String {
+    let (days, time) = (seconds / 86400, seconds % 86400);
+    // Civil date from days since the epoch, from Howard Hinnant's `civil_from_days`
+    let z = days as i64 + 719468;
+    let era = z.div_euclid(146097);
+    let day_of_era = z.rem_euclid(146097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z",
+        time / 3600,
+        time % 3600 / 60,
+        time % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn sets_constants_and_context() -> Result<()> {
+        let mut value = json!({"a": 1, "migrationVersion": 1, "list": [0]});
+        let origin = Origin {
+            filename: Some("users.ndjson"),
+            index: 7,
+            key: None,
+        };
+        for assignment in [
+            Assignment::constant("migrationVersion=3")?,
+            Assignment::constant(r#"meta.tags=["x","y"]"#)?,
+            Assignment::constant("list[1]=true")?,
+            Assignment::from_context("_file=filename")?,
+            Assignment::from_context("_line=index")?,
+            Assignment::from_context("_key=key")?,
+        ] {
+            assignment.apply(&mut value, &origin)?;
+        }
+        crate::processor::assert_json(
+            &value,
+            r#"{"a":1,"migrationVersion":3,"list":[0,true],"meta":{"tags":["x","y"]},"_file":"users.ndjson","_line":7,"_key":null}"#,
+        );
+        assert!(
+            Assignment::constant("a.b=1")?
+                .apply(&mut value, &origin)
+                .is_err()
+        );
+        assert!(Assignment::constant("a=text").is_err());
+        assert!(Assignment::from_context("a=later").is_err());
+        assert!(Assignment::constant("*.a=1").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn formats_timestamps() {
+        assert_eq!(rfc3339(0), "1970-01-01T00:00:00Z");
+        assert_eq!(rfc3339(951_782_400), "2000-02-29T00:00:00Z");
+        assert_eq!(rfc3339(1_700_000_000), "2023-11-14T22:13:20Z");
+    }
+}
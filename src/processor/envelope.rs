@@ -0,0 +1,43 @@
+use crate::processor::FieldPath;
+use eyre::{Result, eyre};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{fs::File, io::BufReader, path::Path};
+
+/// The document surrounding a value that was split out with `--at`.
+///
+/// Saved as a sidecar file so `merge` can put the merged value back where it came from.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Envelope {
+    /// Field path of the value that was split out
+    pub at: String,
+    /// The original document, with the split value replaced by `null`
+    pub document: Value,
+}
+
+impl Envelope {
+    pub fn new(at: String, document: Value) -> Self {
+        Self { at, document }
+    }
+
+    pub fn read(path: &Path) -> Result<Self> {
+        let reader = BufReader::new(File::open(path)?);
+        Ok(serde_json::from_reader(reader)?)
+    }
+
+    pub fn write(&self, path: &Path) -> Result<()> {
+        log::info!("Writing envelope {}", path.display());
+        std::fs::write(path, serde_json::to_string_pretty(self)? + "\n")?;
+        Ok(())
+    }
+
+    /// Places `value` back at the original path inside the document
+    pub fn wrap(mut self, value: Value) -> Result<Value> {
+        let slot = self
+            .document
+            .pointer_mut(&self.at.parse::<FieldPath>()?.pointer()?)
+            .ok_or_else(|| eyre!("Envelope has no value at `{}`", self.at))?;
+        *slot = value;
+        Ok(self.document)
+    }
+}
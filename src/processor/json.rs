@@ -1,19 +1,28 @@
 use super::json_text::JsonText;
-use crate::processor::dots_to_slashes;
-use eyre::Result;
-use json_patch::jsonptr::Pointer;
-use rayon::prelude::*;
+use crate::{
+    filename,
+    input::index_key,
+    output::JsonFormat,
+    processor::{Assignment, FieldPath, KeyCase, Origin, Rename, path},
+};
+use eyre::{Result, eyre};
+use hmac::{Hmac, Mac};
 use regex::Regex;
 use serde_json::Value;
-use std::collections::HashMap;
+use sha2::Sha256;
+
+/// What redacted values are replaced with
+pub const REDACTED: &str = "[REDACTED]";
 
 /// Contains the JSON data as a serde_json::Value and provides utility functions for manipulating fields
 ///
 /// Provides methods for:
 /// - Escaping/unescaping specific fields
-/// - Dropping fields
+/// - Keeping or dropping fields
+/// - Redacting or pseudonymizing values
+/// - Renaming fields and converting the case of keys
+/// - Setting fields to constants or to where the record came from
 /// - Converting to/from serde_json::Value
-
 pub struct Json {
     pub value: serde_json::Value,
 }
@@ -23,58 +32,118 @@ impl Json {
         Json { value }
     }
 
-    pub fn unescape(mut self, fields: Option<&Vec<String>>) -> Self {
+    pub fn unescape(mut self, fields: Option<&Vec<FieldPath>>) -> Self {
         log::debug!("Unescaping fields: {:?}", fields);
         if let Some(fields) = fields {
             fields.iter().for_each(|field| {
-                self.value
-                    .pointer_mut(&dots_to_slashes(field))
-                    .map(|value| {
-                        log::debug!("Unescaping field {}", field);
-                        *value = JsonText::from(value.clone()).unescape();
-                    });
+                field.update(&mut self.value, |value| {
+                    log::debug!("Unescaping field {}", field);
+                    JsonText::from(value).unescape()
+                });
             });
         };
         self
     }
 
-    pub fn escape(mut self, fields: Option<&Vec<String>>) -> Self {
+    pub fn escape(mut self, fields: Option<&Vec<FieldPath>>) -> Self {
         log::debug!("Escaping fields: {:?}", fields);
         if let Some(fields) = fields {
             fields.iter().for_each(|field| {
-                self.value
-                    .pointer_mut(&dots_to_slashes(field))
-                    .map(|value| {
-                        log::debug!("Escaping field {}", field);
-                        *value = JsonText::from(value.clone()).escape();
-                    });
+                field.update(&mut self.value, |value| {
+                    log::debug!("Escaping field {}", field);
+                    JsonText::from(value).escape()
+                });
             });
         };
         self
     }
 
-    pub fn drop(mut self, fields: Option<&Vec<String>>) -> Self {
+    /// Keeps only the matching fields, at their original nesting
+    pub fn keep(mut self, fields: Option<&Vec<FieldPath>>) -> Self {
+        log::debug!("Keeping fields: {:?}", fields);
+        if let Some(fields) = fields {
+            self.value = path::project(&self.value, fields);
+        }
+        self
+    }
+
+    pub fn drop(mut self, fields: Option<&Vec<FieldPath>>) -> Self {
         log::debug!("Dropping fields: {:?}", fields);
+        if let Some(fields) = fields {
+            fields
+                .iter()
+                .for_each(|field| field.delete(&mut self.value));
+        };
+        self
+    }
+
+    /// Replaces the matching values with a fixed mask, keeping the shape of the record
+    pub fn redact(mut self, fields: Option<&Vec<FieldPath>>) -> Self {
+        log::debug!("Redacting fields: {:?}", fields);
         if let Some(fields) = fields {
             fields.iter().for_each(|field| {
-                let str = dots_to_slashes(field);
-                if let Ok(ptr) = Pointer::parse(&str) {
-                    ptr.delete(&mut self.value);
-                }
+                field.update(&mut self.value, |_| Value::String(REDACTED.to_string()))
             });
         };
         self
     }
 
+    /// Replaces the matching values with their pseudonym
+    pub fn pseudonymize(mut self, pseudonymizer: Option<&Pseudonymizer>) -> Result<Self> {
+        if let Some(pseudonymizer) = pseudonymizer {
+            log::debug!("Pseudonymizing fields: {:?}", pseudonymizer.fields);
+            let mut error = None;
+            pseudonymizer.fields.iter().for_each(|field| {
+                field.update(&mut self.value, |value| match pseudonymizer.token(&value) {
+                    Ok(token) => Value::String(token),
+                    Err(e) => {
+                        error.get_or_insert(e);
+                        value
+                    }
+                })
+            });
+            if let Some(e) = error {
+                return Err(e.into());
+            }
+        }
+        Ok(self)
+    }
+
+    /// Moves fields to their new paths, in the order the renames are given
+    pub fn rename(mut self, renames: Option<&Vec<Rename>>) -> Result<Self> {
+        log::debug!("Renaming fields: {:?}", renames);
+        for rename in renames.into_iter().flatten() {
+            rename.apply(&mut self.value)?;
+        }
+        Ok(self)
+    }
+
+    /// Converts every key of every nested object to `case`
+    pub fn key_case(mut self, case: Option<KeyCase>) -> Result<Self> {
+        if let Some(case) = case {
+            self.value = case.apply(self.value)?;
+        }
+        Ok(self)
+    }
+
+    /// Sets fields to constants or to values from the record's `origin`
+    pub fn set(mut self, assignments: Option<&Vec<Assignment>>, origin: &Origin) -> Result<Self> {
+        log::debug!("Setting fields: {:?}", assignments);
+        for assignment in assignments.into_iter().flatten() {
+            assignment.apply(&mut self.value, origin)?;
+        }
+        Ok(self)
+    }
+
     pub fn filter(mut self, filter: Option<&String>) -> Result<Self> {
         log::debug!("Filtering keys: {:?}", filter);
         if let Some(filter) = filter {
-            let regex = Regex::new(&filter)?;
+            let regex = Regex::new(filter)?;
             log::info!("Regex key filter: {:?}", regex);
             self.value = self
                 .value
                 .as_object()
-                .expect("Expected object")
+                .ok_or_else(|| eyre!("Expected an object"))?
                 .iter()
                 .filter(|(key, _)| regex.is_match(key))
                 .map(|(key, value)| (key.clone(), value.clone()))
@@ -83,10 +152,48 @@ impl Json {
         Ok(self)
     }
 
+    /// Splits an object into its key/value pairs, or an array into elements keyed by index
     pub fn entries(self) -> Result<Entries> {
-        let mut entries: HashMap<String, Value> = serde_json::from_value(self.value)?;
-        let entries = entries.par_drain().collect::<Vec<(String, Value)>>();
-        Ok(Entries::new(entries))
+        if let Value::Array(array) = self.value {
+            let entries = array
+                .into_iter()
+                .enumerate()
+                .map(|(i, value)| (index_key(i), value))
+                .collect();
+            return Ok(Entries::new(entries));
+        }
+        match self.value {
+            Value::Object(object) => Ok(Entries::new(object.into_iter().collect())),
+            _ => Err(eyre!("Expected an object or array")),
+        }
+    }
+
+    /// Splits the object into entries, recursing into object values up to `depth` levels.
+    ///
+    /// Nested keys are encoded as filenames and joined with `/`, so each entry maps onto
+    /// a path in a directory tree. Top-level keys are used as they are.
+    pub fn nested_entries(self, depth: usize) -> Result<Entries> {
+        fn nest(list: &mut Vec<(String, Value)>, path: String, value: Value, depth: usize) {
+            match value {
+                Value::Object(object) if depth > 1 && !object.is_empty() => {
+                    for (key, value) in object {
+                        nest(
+                            list,
+                            format!("{path}/{}", filename::encode(&key)),
+                            value,
+                            depth - 1,
+                        );
+                    }
+                }
+                value => list.push((path, value)),
+            }
+        }
+
+        let mut list = Vec::new();
+        for (key, value) in self.entries()?.list() {
+            nest(&mut list, key, value, depth);
+        }
+        Ok(Entries::new(list))
     }
 
     pub fn value(self) -> Value {
@@ -94,6 +201,40 @@ impl Json {
     }
 }
 
+/// Replaces values with a keyed hash, so the same value maps to the same token in every
+/// record and file, but can't be recovered without the key
+#[derive(Clone)]
+pub struct Pseudonymizer {
+    fields: Vec<FieldPath>,
+    key: Vec<u8>,
+}
+
+impl Pseudonymizer {
+    pub fn new(fields: Vec<FieldPath>, key: impl Into<Vec<u8>>) -> Self {
+        Self {
+            fields,
+            key: key.into(),
+        }
+    }
+
+    /// The hex HMAC-SHA256 of the value's canonical JSON, so `"1"` and `1` get different tokens
+    pub fn token(&self, value: &Value) -> std::io::Result<String> {
+        let message = JsonFormat::canonical().to_string(value)?;
+        Ok(hmac_sha256(&self.key, message.as_bytes())
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect())
+    }
+}
+
+/// HMAC as specified by RFC 2104, with SHA-256
+fn hmac_sha256(key: &[u8], message: &[u8]) -> [u8; 32] {
+    // HMAC accepts keys of any length, so this can't fail
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts any key length");
+    mac.update(message);
+    mac.finalize().into_bytes().into()
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Entries {
     list: Vec<(String, Value)>,
@@ -104,7 +245,7 @@ impl Entries {
         Entries { list }
     }
 
-    pub fn drop(mut self, fields: Option<&Vec<String>>) -> Self {
+    pub fn drop(mut self, fields: Option<&Vec<FieldPath>>) -> Self {
         self.list
             .iter_mut()
             .map(|(_, value)| {
@@ -116,17 +257,13 @@ impl Entries {
     }
 
     pub fn filter(mut self, fields: Option<&String>) -> Result<Self> {
-        self.list = self
-            .list
-            .into_iter()
-            .filter(|(key, _)| match fields {
-                Some(filter) => {
-                    let regex = Regex::new(filter).expect("Invalid regex");
-                    regex.is_match(key)
-                }
-                None => true,
-            })
-            .collect();
+        self.list.retain(|(key, _)| match fields {
+            Some(filter) => {
+                let regex = Regex::new(filter).expect("Invalid regex");
+                regex.is_match(key)
+            }
+            None => true,
+        });
         Ok(self)
     }
 
@@ -158,9 +295,7 @@ impl From<Json> for serde_json::Value {
 impl From<Vec<(String, Value)>> for Json {
     fn from(value: Vec<(String, Value)>) -> Self {
         Json {
-            value: serde_json::Value::Object(
-                value.into_iter().map(|(k, v)| (k, v.into())).collect(),
-            ),
+            value: serde_json::Value::Object(value.into_iter().collect()),
         }
     }
 }
@@ -239,6 +374,26 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn split_nested() -> Result<()> {
+        let object = json!({
+            "a": {"b": {"c": 1}, "d": 2, "e": {}},
+            "f": 3
+        });
+        let mut entries = Json::from(object).nested_entries(2)?.list();
+        entries.sort_unstable_by_key(|(key, _)| key.clone());
+        assert_eq!(
+            entries,
+            vec![
+                ("a/b".to_string(), json!({"c": 1})),
+                ("a/d".to_string(), json!(2)),
+                ("a/e".to_string(), json!({})),
+                ("f".to_string(), json!(3)),
+            ]
+        );
+        Ok(())
+    }
+
     #[test]
     fn split_unfiltered() -> Result<()> {
         let object = json!({
@@ -247,9 +402,7 @@ mod tests {
             "c": "3"
         });
         let filter = None;
-        let mut entries = Json::from(object).entries()?.filter(filter)?.list();
-        // Split's output is non-deterministic, so we sort it to compare
-        entries.sort_unstable_by_key(|(key, _)| key.clone());
+        let entries = Json::from(object).entries()?.filter(filter)?.list();
         assert_eq!(
             entries,
             vec![
@@ -260,4 +413,61 @@ mod tests {
         );
         Ok(())
     }
+
+    #[test]
+    #[cfg(feature = "preserve_order")]
+    fn keeps_key_order() -> Result<()> {
+        let object: Value =
+            serde_json::from_str(r#"{"c": 1, "a": {"z": 2, "y": 3, "x": 4}, "b": 5}"#)?;
+        let entries = Json::from(object.clone()).entries()?.list();
+        let keys: Vec<&str> = entries.iter().map(|(key, _)| key.as_str()).collect();
+        assert_eq!(keys, ["c", "a", "b"]);
+
+        let dropped = Json::from(object).drop(Some(&vec!["a.z".parse()?])).value();
+        assert_eq!(dropped.to_string(), r#"{"c":1,"a":{"y":3,"x":4},"b":5}"#);
+        Ok(())
+    }
+
+    #[test]
+    fn tokens_depend_on_the_value_and_its_type() -> Result<()> {
+        let pseudonymizer = Pseudonymizer::new(vec![], "secret");
+        let token = |value: Value| pseudonymizer.token(&value);
+        // The same key gives the same token in every record and every run
+        assert_eq!(
+            token(json!("1"))?,
+            Pseudonymizer::new(vec![], "secret").token(&json!("1"))?
+        );
+        assert_ne!(token(json!("1"))?, token(json!(1))?);
+        assert_ne!(token(json!(1))?, token(json!(true))?);
+        // Objects are hashed as canonical JSON, so key order and number formatting don't matter
+        assert_eq!(
+            token(json!({"a": 1, "b": 2}))?,
+            token(json!({"b": 2, "a": 1}))?
+        );
+        assert_eq!(token(json!(1.0))?, token(json!(1))?);
+        Ok(())
+    }
+
+    #[test]
+    fn redacts_and_pseudonymizes() -> Result<()> {
+        let value = json!({"users": [
+            {"email": "a@example.com", "name": "A", "age": 1},
+            {"email": "a@example.com", "name": "B", "age": 2}
+        ]});
+        let pseudonymizer = Pseudonymizer::new(vec!["users[*].email".parse()?], "secret");
+        let result = Json::from(value)
+            .redact(Some(&vec!["users[*].name".parse()?]))
+            .pseudonymize(Some(&pseudonymizer))?
+            .value();
+        let users = result["users"].as_array().unwrap();
+        assert_eq!(users[0]["name"], REDACTED);
+        assert_eq!(users[1]["age"], 2);
+        assert_eq!(users[0]["email"], users[1]["email"]);
+        assert_eq!(users[0]["email"].as_str().unwrap().len(), 64);
+        assert_ne!(
+            users[0]["email"],
+            Pseudonymizer::new(vec![], "other").token(&json!("a@example.com"))?
+        );
+        Ok(())
+    }
 }
@@ -12,7 +12,6 @@ use serde_json::Value;
 /// With two conversion functions:
 /// - `unescape`: Converts string-escaped JSON into a `Value`.
 /// - `escape`: Converts a `Value` into a string-escaped JSON `String`.
-
 pub enum JsonText {
     String(String),
     Value(Value),
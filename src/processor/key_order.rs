@@ -0,0 +1,140 @@
+use eyre::{Report, Result, eyre};
+use serde_json::Value;
+use std::cmp::Ordering;
+
+/// How the keys of an object are sorted, after any pinned keys
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum KeySort {
+    /// Keep the order keys were read in
+    #[default]
+    Preserve,
+    /// Sort keys alphabetically
+    Alpha,
+    /// Sort keys alphabetically, comparing runs of digits by value, so `item2` is before `item10`
+    Natural,
+}
+
+impl std::str::FromStr for KeySort {
+    type Err = Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "preserve" => Ok(Self::Preserve),
+            "alpha" => Ok(Self::Alpha),
+            "natural" => Ok(Self::Natural),
+            s => Err(eyre!(
+                "Unknown key sort `{s}`, expected preserve, alpha or natural"
+            )),
+        }
+    }
+}
+
+/// Orders the keys of every object written, at every level of nesting.
+///
+/// Pinned keys come first in the order they are listed, followed by the rest of the keys
+/// sorted by `sort`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct KeyOrder {
+    sort: KeySort,
+    pinned: Vec<String>,
+}
+
+impl KeyOrder {
+    pub fn new(sort: KeySort, pinned: Option<Vec<String>>) -> Self {
+        Self {
+            sort,
+            pinned: pinned.unwrap_or_default(),
+        }
+    }
+
+    /// Whether keys are kept in the order they were read
+    pub fn is_preserve(&self) -> bool {
+        self.sort == KeySort::Preserve && self.pinned.is_empty()
+    }
+
+    pub fn compare(&self, a: &str, b: &str) -> Ordering {
+        let rank = |key: &str| self.pinned.iter().position(|pinned| pinned == key);
+        // `None` sorts before `Some`, so unpinned keys are ranked by `usize::MAX`
+        let rank = |key| rank(key).unwrap_or(usize::MAX);
+        rank(a).cmp(&rank(b)).then_with(|| match self.sort {
+            KeySort::Preserve => Ordering::Equal,
+            KeySort::Alpha => a.cmp(b),
+            KeySort::Natural => natural_cmp(a, b),
+        })
+    }
+
+    /// Orders a list of top-level entries by their key
+    pub fn sort_entries(&self, entries: &mut [(String, Value)]) {
+        if !self.is_preserve() {
+            entries.sort_by(|(a, _), (b, _)| self.compare(a, b));
+        }
+    }
+}
+
+/// Compares strings character by character, except runs of ASCII digits are compared by value
+pub fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let digits = |s: &str| s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (mut a, mut b) = (a, b);
+    loop {
+        let (Some(x), Some(y)) = (a.chars().next(), b.chars().next()) else {
+            return a.len().cmp(&b.len());
+        };
+        if x.is_ascii_digit() && y.is_ascii_digit() {
+            let (run_a, rest_a) = a.split_at(digits(a));
+            let (run_b, rest_b) = b.split_at(digits(b));
+            let (value_a, value_b) = (run_a.trim_start_matches('0'), run_b.trim_start_matches('0'));
+            let ordering = value_a
+                .len()
+                .cmp(&value_b.len())
+                .then_with(|| value_a.cmp(value_b))
+                // Fewer leading zeros first, so `1` is before `01`
+                .then_with(|| run_a.len().cmp(&run_b.len()));
+            if ordering.is_ne() {
+                return ordering;
+            }
+            (a, b) = (rest_a, rest_b);
+        } else if x != y {
+            return x.cmp(&y);
+        } else {
+            (a, b) = (&a[x.len_utf8()..], &b[y.len_utf8()..]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compares_digit_runs_by_value() {
+        let mut names = ["item10", "item2", "item1", "item02", "alpha", "item"];
+        names.sort_by(|a, b| natural_cmp(a, b));
+        assert_eq!(
+            names,
+            ["alpha", "item", "item1", "item2", "item02", "item10"]
+        );
+    }
+
+    #[test]
+    fn pins_keys_then_sorts() {
+        let order = KeyOrder::new(
+            KeySort::Natural,
+            Some(vec!["id".to_string(), "type".to_string()]),
+        );
+        let mut keys = ["name", "item10", "type", "item2", "id"];
+        keys.sort_by(|a, b| order.compare(a, b));
+        assert_eq!(keys, ["id", "type", "item2", "item10", "name"]);
+    }
+
+    #[test]
+    fn preserves_by_default() {
+        let order = KeyOrder::default();
+        assert!(order.is_preserve());
+        let mut entries = vec![
+            ("bravo".to_string(), Value::Null),
+            ("alpha".to_string(), Value::Null),
+        ];
+        order.sort_entries(&mut entries);
+        assert_eq!(entries[0].0, "bravo");
+    }
+}
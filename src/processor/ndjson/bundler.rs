@@ -1,34 +1,52 @@
 use crate::{
-    input::{InputDirectory, JsonSource},
+    input::{EntryFilter, EntryOrder, JsonSourceInput},
     output::JsonAppendableOutput,
-    processor::json::Json,
+    processor::{FieldPath, Origin, Transforms, json::Json},
 };
 use eyre::{Result, eyre};
+use serde_json::Value;
+use std::path::Path;
 
 pub struct BundlerBuilder {
-    input: InputDirectory,
+    input: JsonSourceInput,
     output: JsonAppendableOutput,
-    escape_fields: Option<Vec<String>>,
-    drop_fields: Option<Vec<String>>,
+    escape_fields: Option<Vec<FieldPath>>,
+    transforms: Transforms,
+    filter: EntryFilter,
+    recursive: bool,
 }
 
 impl BundlerBuilder {
-    pub fn new(input: InputDirectory, output: JsonAppendableOutput) -> Self {
+    pub fn new(input: JsonSourceInput, output: JsonAppendableOutput) -> Self {
         Self {
             input,
             output,
             escape_fields: None,
-            drop_fields: None,
+            transforms: Transforms::new(),
+            filter: EntryFilter::new(),
+            recursive: false,
         }
     }
 
-    pub fn escape_fields(mut self, fields: Option<Vec<String>>) -> Self {
+    pub fn escape_fields(mut self, fields: Option<Vec<FieldPath>>) -> Self {
         self.escape_fields = fields;
         self
     }
 
-    pub fn drop_fields(mut self, fields: Option<Vec<String>>) -> Self {
-        self.drop_fields = fields;
+    /// Field transforms to run on every record, after escaping
+    pub fn transforms(mut self, transforms: Transforms) -> Self {
+        self.transforms = transforms;
+        self
+    }
+
+    pub fn filter(mut self, filter: EntryFilter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    /// Bundle files in subdirectories too
+    pub fn recursive(mut self, recursive: bool) -> Self {
+        self.recursive = recursive;
         self
     }
 
@@ -37,16 +55,20 @@ impl BundlerBuilder {
             input: self.input,
             output: self.output,
             escape_fields: self.escape_fields,
-            drop_fields: self.drop_fields,
+            transforms: self.transforms,
+            filter: self.filter,
+            recursive: self.recursive,
         }
     }
 }
 
 pub struct Bundler {
-    input: InputDirectory,
+    input: JsonSourceInput,
     output: JsonAppendableOutput,
-    escape_fields: Option<Vec<String>>,
-    drop_fields: Option<Vec<String>>,
+    escape_fields: Option<Vec<FieldPath>>,
+    transforms: Transforms,
+    filter: EntryFilter,
+    recursive: bool,
 }
 
 impl Bundler {
@@ -55,15 +77,27 @@ impl Bundler {
             .output
             .read()
             .map_err(|e| eyre!("Error acquiring read lock on output: {}", e))?;
-        self.input
-            .get_entries(false)
-            .drain(..)
-            .try_for_each(|(_name, value)| {
-                let json = Json::from(value)
-                    .escape(self.escape_fields.as_ref())
-                    .drop(self.drop_fields.as_ref())
-                    .value();
-                output.append(json).map_err(|e| eyre!(e))
-            })
+        let mut index = 0;
+        let mut append = |name: String, path: &Path, value: Value| -> Result<()> {
+            let path = path.to_string_lossy();
+            let origin = Origin {
+                filename: Some(&path),
+                index,
+                key: Some(&name),
+            };
+            index += 1;
+            let json = Json::from(value).escape(self.escape_fields.as_ref());
+            let json = self.transforms.apply(json, &origin)?.value();
+            output.append(json).map_err(|e| eyre!(e))
+        };
+        self.input.visit_flat_entries(
+            EntryOrder::Default,
+            self.recursive,
+            &self.filter,
+            &mut append,
+        )?;
+        output
+            .finish()
+            .map_err(|e| eyre!("Error writing to output: {e}"))
     }
 }
@@ -1,7 +1,8 @@
 use crate::{
+    filename,
     input::JsonReaderInput,
     output::JsonWritableOutput,
-    processor::{dots_to_slashes, json::Json},
+    processor::{FieldPath, NameTemplate, Origin, Transforms, field_to_name, json::Json},
 };
 use eyre::{Result, eyre};
 use serde_json::Value;
@@ -9,10 +10,12 @@ use serde_json::Value;
 pub struct UnbundlerBuilder {
     input: JsonReaderInput,
     output: JsonWritableOutput,
-    filename: Option<Vec<String>>,
-    unescape_fields: Option<Vec<String>>,
-    drop_fields: Option<Vec<String>>,
-    type_field: Option<String>,
+    filename: Option<Vec<FieldPath>>,
+    unescape_fields: Option<Vec<FieldPath>>,
+    transforms: Transforms,
+    type_field: Option<FieldPath>,
+    records_at: Option<String>,
+    name_template: Option<NameTemplate>,
 }
 
 impl UnbundlerBuilder {
@@ -22,39 +25,57 @@ impl UnbundlerBuilder {
             output,
             filename: None,
             unescape_fields: None,
-            drop_fields: None,
+            transforms: Transforms::new(),
             type_field: None,
+            records_at: None,
+            name_template: None,
         }
     }
 
-    pub fn filename(mut self, filename: Option<Vec<String>>) -> Self {
+    pub fn filename(mut self, filename: Option<Vec<FieldPath>>) -> Self {
         self.filename = filename;
         self
     }
 
-    pub fn unescape_fields(mut self, fields: Option<Vec<String>>) -> Self {
+    pub fn unescape_fields(mut self, fields: Option<Vec<FieldPath>>) -> Self {
         self.unescape_fields = fields;
         self
     }
 
-    pub fn drop_fields(mut self, fields: Option<Vec<String>>) -> Self {
-        self.drop_fields = fields;
+    /// Field transforms to run on every record, after unescaping and naming it
+    pub fn transforms(mut self, transforms: Transforms) -> Self {
+        self.transforms = transforms;
         self
     }
 
-    pub fn type_field(mut self, field: Option<String>) -> Self {
+    pub fn type_field(mut self, field: Option<FieldPath>) -> Self {
         self.type_field = field;
         self
     }
 
+    /// Reads records from the array at this field path of a single JSON document,
+    /// instead of one record per line
+    pub fn records_at(mut self, path: Option<String>) -> Self {
+        self.records_at = path;
+        self
+    }
+
+    /// Names each record from a template instead of `filename` and `type_field`
+    pub fn name_template(mut self, template: Option<NameTemplate>) -> Self {
+        self.name_template = template;
+        self
+    }
+
     pub fn build(self) -> Unbundler {
         Unbundler {
             input: self.input,
             output: self.output,
             filename: self.filename,
             unescape_fields: self.unescape_fields,
-            drop_fields: self.drop_fields,
+            transforms: self.transforms,
             type_field: self.type_field,
+            records_at: self.records_at,
+            name_template: self.name_template,
         }
     }
 }
@@ -62,10 +83,12 @@ impl UnbundlerBuilder {
 pub struct Unbundler {
     input: JsonReaderInput,
     output: JsonWritableOutput,
-    filename: Option<Vec<String>>,
-    unescape_fields: Option<Vec<String>>,
-    drop_fields: Option<Vec<String>>,
-    type_field: Option<String>,
+    filename: Option<Vec<FieldPath>>,
+    unescape_fields: Option<Vec<FieldPath>>,
+    transforms: Transforms,
+    type_field: Option<FieldPath>,
+    records_at: Option<String>,
+    name_template: Option<NameTemplate>,
 }
 
 impl Unbundler {
@@ -76,59 +99,95 @@ impl Unbundler {
     /// * `input` - A refeence to an `Input` representing the source of NDJSON data.
     /// * `output` - A reference to an `Output` where the JSON files will be written.
     /// * `name` - An optional name for the JSON objects, used as a key to extract values.
-
     pub fn unbundle(&self) -> Result<()> {
         let mut i: usize = 0;
-        let name_list = match &self.filename {
-            Some(list) => list
-                .iter()
-                .map(|name| dots_to_slashes(&name))
-                .collect::<Vec<String>>(),
-            None => vec![],
-        };
-        let type_field = self
-            .type_field
-            .as_ref()
-            .map(|field| dots_to_slashes(&field));
-
-        let name_entry = |i: usize, value: &Value| {
+        let name_entry = |i: usize, value: &Value| -> Result<String> {
             let default_name = format!("object-{i:06}");
+            if let Some(template) = &self.name_template {
+                return template.render(value, &default_name, i);
+            }
 
-            let name = name_list
+            let name = self
+                .filename
                 .iter()
-                .find_map(|name| value.pointer(name))
-                .map_or(default_name, |value| {
-                    value.as_str().unwrap_or_default().to_string()
-                });
-
-            match &type_field {
-                Some(field) => value.pointer(&field).map_or(name.clone(), |value| {
-                    format!("{name}.{}", value.as_str().unwrap_or_default().to_string())
-                }),
+                .flatten()
+                .find_map(|name| field_to_name(value, name))
+                .map_or(default_name, |name| filename::encode(&name));
+
+            Ok(match &self.type_field {
+                Some(field) => match field_to_name(value, field) {
+                    Some(suffix) => format!("{name}.{}", filename::encode(&suffix)),
+                    None => name,
+                },
                 None => name,
-            }
+            })
+        };
+
+        let source_name = self.input.source_name();
+        // `position` is the line number of an NDJSON record, or the index of a `records_at` record
+        let write_record = |i: usize, position: usize, json: Json| -> Result<()> {
+            let json = json.unescape(self.unescape_fields.as_ref());
+            // Name the record as it was read, so fields dropped or renamed below still count
+            let name = name_entry(i, &json.value)?;
+            let origin = Origin {
+                filename: Some(&source_name),
+                index: position,
+                key: Some(&name),
+            };
+            let json = self.transforms.apply(json, &origin)?.value();
+            let entry = vec![(name, json)];
+            self.output
+                .read()
+                .map_err(|_| eyre!("Error acquiring read lock on output"))?
+                .write_entries(entry)?;
+            Ok(())
         };
 
+        if let Some(records_at) = &self.records_at {
+            self.input.for_each_entry_at(records_at, &mut |_, value| {
+                write_record(i, i, Json::from(value))?;
+                i += 1;
+                Ok(())
+            })?;
+            return self.finish(i);
+        }
+
         let mut buf = String::new();
-        while let Ok(()) = self.input.read_line(&mut buf) {
+        let mut line: usize = 0;
+        loop {
+            buf.clear();
+            self.input.read_line(&mut buf)?;
+            if buf.is_empty() {
+                break;
+            }
+            // Blank lines are skipped, but still counted, so records keep their line numbers
+            line += 1;
+            if buf.trim().is_empty() {
+                continue;
+            }
             match Json::try_from(&buf) {
-                Ok(json) => {
-                    let json = json
-                        .unescape(self.unescape_fields.as_ref())
-                        .drop(self.drop_fields.as_ref())
-                        .value();
-                    let entry = vec![(name_entry(i, &json), json)];
-                    self.output
-                        .read()
-                        .map_err(|_| eyre!("Error acquiring read lock on output"))?
-                        .write_entries(entry)?
-                }
-                Err(e) if serde_json::Error::is_eof(&e) => break,
-                Err(e) => log::error!("Failed to parse line {}: {}", i, e),
+                Ok(json) => write_record(i, line, json)?,
+                Err(e) => log::error!("Failed to parse line {line}: {e}"),
             }
-            buf.clear();
             i += 1;
         }
+        self.finish(i)
+    }
+
+    /// Finishes the output, then logs how many records were read and how many of them
+    /// collided on their name
+    fn finish(&self, records: usize) -> Result<()> {
+        let output = self
+            .output
+            .read()
+            .map_err(|_| eyre!("Error acquiring read lock on output"))?;
+        output.finish()?;
+        let collisions = output.collisions();
+        match collisions {
+            0 => log::info!("Unbundled {records} records"),
+            1 => log::warn!("Unbundled {records} records, 1 name collision"),
+            n => log::warn!("Unbundled {records} records, {n} name collisions"),
+        }
         Ok(())
     }
 }
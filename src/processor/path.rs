@@ -0,0 +1,476 @@
+use eyre::{Report, Result, eyre};
+use regex::Regex;
+use serde_json::{Map, Value};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    iter::Peekable,
+    str::Chars,
+};
+
+/// A path to one or more fields of a JSON value, like `objects[*].attributes.**._secret`.
+///
+/// Segments are separated by `.` and can be:
+/// - a key, with `\` escaping the next character, like `a\.b`
+/// - a quoted key, like `"a.b"` or `'a/b'`
+/// - an array index, like `[3]`, or a number, like `.3`
+/// - `*` or `[*]` for every key of an object or element of an array
+/// - `**` for any number of nested levels, including none
+/// - a regex between slashes, like `/^_/`, for every object key it matches
+#[derive(Clone, Debug)]
+pub struct FieldPath {
+    source: String,
+    segments: Vec<Segment>,
+}
+
+#[derive(Clone, Debug)]
+enum Segment {
+    /// An object key, or an array index when it is a number
+    Key(String),
+    Index(usize),
+    Any,
+    Descend,
+    Matches(Regex),
+}
+
+/// One step from a value to its child
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+enum Step {
+    Key(String),
+    Index(usize),
+}
+
+impl FieldPath {
+    /// The keys and indices of a path that selects a single value, like `--at` needs
+    pub fn tokens(&self) -> Result<Vec<String>> {
+        self.segments
+            .iter()
+            .map(|segment| match segment {
+                Segment::Key(key) => Ok(key.clone()),
+                Segment::Index(index) => Ok(index.to_string()),
+                _ => Err(eyre!(
+                    "Field path `{}` must select a single value, without wildcards or regexes",
+                    self.source
+                )),
+            })
+            .collect()
+    }
+
+    /// The JSON pointer of a path that selects a single value
+    pub fn pointer(&self) -> Result<String> {
+        Ok(self
+            .tokens()?
+            .iter()
+            .map(|token| format!("/{}", token.replace('~', "~0").replace('/', "~1")))
+            .collect())
+    }
+
+    /// Every value the path matches, in document order
+    pub fn select<'a>(&self, value: &'a Value) -> Vec<&'a Value> {
+        self.locate(value)
+            .iter()
+            .filter_map(|steps| get(value, steps))
+            .collect()
+    }
+
+    /// Replaces every value the path matches with the result of `f`
+    pub fn update(&self, value: &mut Value, mut f: impl FnMut(Value) -> Value) {
+        for steps in self.locate(value) {
+            if let Some(target) = get_mut(value, &steps) {
+                *target = f(target.take());
+            }
+        }
+    }
+
+    /// Removes every value the path matches, keeping the order of the remaining keys
+    pub fn delete(&self, value: &mut Value) {
+        let mut found = self.locate(value);
+        // Children before their parents, and later array elements before earlier ones,
+        // so removing one match doesn't move the others
+        found.sort_unstable_by(|a, b| b.cmp(a));
+        for steps in found {
+            let Some((last, parent)) = steps.split_last() else {
+                continue;
+            };
+            match (get_mut(value, parent), last) {
+                // `Map::remove` swaps the last key into the removed slot when order is preserved
+                (Some(Value::Object(object)), Step::Key(key)) => {
+                    object.retain(|name, _| name != key)
+                }
+                (Some(Value::Array(array)), Step::Index(index)) if *index < array.len() => {
+                    array.remove(*index);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// The steps to every value the path matches, without duplicates
+    fn locate(&self, value: &Value) -> Vec<Vec<Step>> {
+        let mut found = Vec::new();
+        walk(value, &self.segments, &mut Vec::new(), &mut found);
+        let mut seen = HashSet::new();
+        found.retain(|steps| seen.insert(steps.clone()));
+        found
+    }
+}
+
+/// Copies only the values that any of `paths` match, at their original nesting and in
+/// their original order. Arrays keep just their selected elements.
+pub fn project(value: &Value, paths: &[FieldPath]) -> Value {
+    let mut selection = Selection::default();
+    for path in paths {
+        for steps in path.locate(value) {
+            steps
+                .into_iter()
+                .fold(&mut selection, |node, step| {
+                    node.children.entry(step).or_default()
+                })
+                .whole = true;
+        }
+    }
+    selection.project(value)
+}
+
+/// The matched values of a [`project`], as a tree of steps from the root
+#[derive(Default)]
+struct Selection {
+    whole: bool,
+    children: HashMap<Step, Selection>,
+}
+
+impl Selection {
+    fn project(&self, value: &Value) -> Value {
+        match value {
+            _ if self.whole => value.clone(),
+            Value::Object(object) => object
+                .iter()
+                .filter_map(|(key, child)| {
+                    let selection = self.children.get(&Step::Key(key.clone()))?;
+                    Some((key.clone(), selection.project(child)))
+                })
+                .collect(),
+            Value::Array(array) => array
+                .iter()
+                .enumerate()
+                .filter_map(|(index, child)| {
+                    Some(self.children.get(&Step::Index(index))?.project(child))
+                })
+                .collect(),
+            scalar => scalar.clone(),
+        }
+    }
+}
+
+/// Walks `tokens` from `value`, adding an empty object for each missing key. Fails with
+/// the first token that has no object or array to hold it.
+pub(crate) fn make_path<'a, 't>(
+    value: &'a mut Value,
+    tokens: &'t [String],
+) -> std::result::Result<&'a mut Value, &'t str> {
+    tokens.iter().try_fold(value, |value, token| match value {
+        Value::Object(object) => Ok(object
+            .entry(token.clone())
+            .or_insert_with(|| Value::Object(Map::new()))),
+        Value::Array(array) => token
+            .parse::<usize>()
+            .ok()
+            .and_then(|index| array.get_mut(index))
+            .ok_or(token.as_str()),
+        _ => Err(token.as_str()),
+    })
+}
+
+fn walk(value: &Value, segments: &[Segment], prefix: &mut Vec<Step>, found: &mut Vec<Vec<Step>>) {
+    let Some((segment, rest)) = segments.split_first() else {
+        found.push(prefix.clone());
+        return;
+    };
+    // `**` matches no levels at all, or descends one level and tries again
+    let rest = match segment {
+        Segment::Descend => {
+            walk(value, rest, prefix, found);
+            segments
+        }
+        _ => rest,
+    };
+    for (step, child) in children(segment, value) {
+        prefix.push(step);
+        walk(child, rest, prefix, found);
+        prefix.pop();
+    }
+}
+
+/// The children of `value` that `segment` matches
+fn children<'a>(segment: &Segment, value: &'a Value) -> Vec<(Step, &'a Value)> {
+    match (segment, value) {
+        (Segment::Key(key), Value::Object(object)) => object
+            .get(key)
+            .map(|child| (Step::Key(key.clone()), child))
+            .into_iter()
+            .collect(),
+        (Segment::Key(key), Value::Array(array)) => key
+            .parse::<usize>()
+            .ok()
+            .and_then(|index| Some((Step::Index(index), array.get(index)?)))
+            .into_iter()
+            .collect(),
+        (Segment::Index(index), Value::Array(array)) => array
+            .get(*index)
+            .map(|child| (Step::Index(*index), child))
+            .into_iter()
+            .collect(),
+        (Segment::Any | Segment::Descend, Value::Object(object)) => object
+            .iter()
+            .map(|(key, child)| (Step::Key(key.clone()), child))
+            .collect(),
+        (Segment::Any | Segment::Descend, Value::Array(array)) => array
+            .iter()
+            .enumerate()
+            .map(|(index, child)| (Step::Index(index), child))
+            .collect(),
+        (Segment::Matches(regex), Value::Object(object)) => object
+            .iter()
+            .filter(|(key, _)| regex.is_match(key))
+            .map(|(key, child)| (Step::Key(key.clone()), child))
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn get<'a>(value: &'a Value, steps: &[Step]) -> Option<&'a Value> {
+    steps.iter().try_fold(value, |value, step| match step {
+        Step::Key(key) => value.as_object()?.get(key),
+        Step::Index(index) => value.as_array()?.get(*index),
+    })
+}
+
+fn get_mut<'a>(value: &'a mut Value, steps: &[Step]) -> Option<&'a mut Value> {
+    steps.iter().try_fold(value, |value, step| match step {
+        Step::Key(key) => value.as_object_mut()?.get_mut(key),
+        Step::Index(index) => value.as_array_mut()?.get_mut(*index),
+    })
+}
+
+impl std::str::FromStr for FieldPath {
+    type Err = Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        if s.is_empty() {
+            return Err(eyre!("Empty field path"));
+        }
+        let mut chars = s.chars().peekable();
+        let mut segments = Vec::new();
+        loop {
+            let segment = match chars.peek() {
+                Some('[') => {
+                    chars.next();
+                    bracket(&mut chars, s)?
+                }
+                Some(&quote @ ('"' | '\'')) => {
+                    chars.next();
+                    Segment::Key(quoted(&mut chars, quote, s)?)
+                }
+                Some('/') => {
+                    chars.next();
+                    let pattern = quoted(&mut chars, '/', s)?;
+                    Segment::Matches(
+                        Regex::new(&pattern)
+                            .map_err(|e| eyre!("Invalid regex in field path `{s}`: {e}"))?,
+                    )
+                }
+                _ => bare(&mut chars, s)?,
+            };
+            segments.push(segment);
+            match chars.peek() {
+                None => break,
+                Some('.') => {
+                    chars.next();
+                }
+                Some('[') => {}
+                Some(c) => return Err(eyre!("Unexpected `{c}` in field path `{s}`")),
+            }
+        }
+        Ok(Self {
+            source: s.to_string(),
+            segments,
+        })
+    }
+}
+
+/// Reads a key up to the next `.` or `[`, or a `*` or `**` wildcard
+fn bare(chars: &mut Peekable<Chars>, s: &str) -> Result<Segment> {
+    let (mut key, mut escaped) = (String::new(), false);
+    while let Some(&c) = chars.peek() {
+        match c {
+            '.' | '[' => break,
+            '\\' => {
+                chars.next();
+                key.push(
+                    chars
+                        .next()
+                        .ok_or_else(|| eyre!("Field path `{s}` ends with `\\`"))?,
+                );
+                escaped = true;
+                continue;
+            }
+            c => key.push(c),
+        }
+        chars.next();
+    }
+    match (key.as_str(), escaped) {
+        ("", false) => Err(eyre!("Empty segment in field path `{s}`")),
+        ("*", false) => Ok(Segment::Any),
+        ("**", false) => Ok(Segment::Descend),
+        _ => Ok(Segment::Key(key)),
+    }
+}
+
+/// Reads up to the closing `quote`. Between slashes only `\/` is unescaped, so the
+/// regex keeps its own escapes.
+fn quoted(chars: &mut Peekable<Chars>, quote: char, s: &str) -> Result<String> {
+    let mut text = String::new();
+    loop {
+        match chars.next() {
+            Some(c) if c == quote => return Ok(text),
+            Some('\\') => match chars.next() {
+                Some(c) if quote != '/' || c == '/' => text.push(c),
+                Some(c) => text.extend(['\\', c]),
+                None => break,
+            },
+            Some(c) => text.push(c),
+            None => break,
+        }
+    }
+    Err(eyre!("Missing closing `{quote}` in field path `{s}`"))
+}
+
+/// Reads an `[index]`, `[*]` or `["key"]` after the opening bracket
+fn bracket(chars: &mut Peekable<Chars>, s: &str) -> Result<Segment> {
+    let segment = match chars.peek() {
+        Some(&quote @ ('"' | '\'')) => {
+            chars.next();
+            Segment::Key(quoted(chars, quote, s)?)
+        }
+        _ => {
+            let mut text = String::new();
+            while let Some(c) = chars.next_if(|&c| c != ']') {
+                text.push(c);
+            }
+            match text.as_str() {
+                "*" => Segment::Any,
+                text => Segment::Index(
+                    text.parse()
+                        .map_err(|_| eyre!("Invalid index `[{text}]` in field path `{s}`"))?,
+                ),
+            }
+        }
+    };
+    match chars.next() {
+        Some(']') => Ok(segment),
+        _ => Err(eyre!("Missing closing `]` in field path `{s}`")),
+    }
+}
+
+impl fmt::Display for FieldPath {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+impl PartialEq for FieldPath {
+    fn eq(&self, other: &Self) -> bool {
+        self.source == other.source
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn path(s: &str) -> FieldPath {
+        s.parse().expect("Valid field path")
+    }
+
+    #[test]
+    fn parses_quotes_escapes_and_indices() -> Result<()> {
+        assert_eq!(path("a.b").tokens()?, ["a", "b"]);
+        assert_eq!(path(r"a\.b.c").tokens()?, ["a.b", "c"]);
+        assert_eq!(
+            path(r#""a.b"['c/d'][3].4"#).tokens()?,
+            ["a.b", "c/d", "3", "4"]
+        );
+        assert_eq!(path(r#"x["a~b/c"]"#).pointer()?, "/x/a~0b~1c");
+        assert!(path("a.*").tokens().is_err());
+        for invalid in ["", "a..b", "a.", "[x]", "[3", "\"a", "/(/"] {
+            assert!(invalid.parse::<FieldPath>().is_err(), "{invalid}");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn selects_wildcards() {
+        let value = json!({"a": [{"id": 1}, {"id": 2}], "b": {"id": 3, "c": {"id": 4}}});
+        assert_eq!(path("a[*].id").select(&value), [&json!(1), &json!(2)]);
+        assert_eq!(path("a.1.id").select(&value), [&json!(2)]);
+        assert_eq!(
+            path("**.id").select(&value),
+            [&json!(1), &json!(2), &json!(3), &json!(4)]
+        );
+        assert_eq!(path("*.c.id").select(&value), [&json!(4)]);
+        assert_eq!(path("b./^i/").select(&value), [&json!(3)]);
+        assert!(path("a.id").select(&value).is_empty());
+    }
+
+    #[test]
+    fn deletes_every_match() {
+        let mut value: Value = serde_json::from_str(
+            r#"{"objects": [
+                {"id": 1, "attributes": {"_secret": 1, "nested": {"_secret": 2, "keep": 3}}},
+                {"id": 2, "attributes": {"keep": 4, "_secret": 5}}
+            ]}"#,
+        )
+        .unwrap();
+        path("objects[*].attributes.**._secret").delete(&mut value);
+        crate::processor::assert_json(
+            &value,
+            r#"{"objects":[{"id":1,"attributes":{"nested":{"keep":3}}},{"id":2,"attributes":{"keep":4}}]}"#,
+        );
+        let mut array = json!([0, 1, 2, 3]);
+        path("[*]").delete(&mut array);
+        assert_eq!(array, json!([]));
+    }
+
+    #[test]
+    fn projects_selected_fields() {
+        let value: Value = serde_json::from_str(
+            r#"{"type": "t", "id": 1, "attributes": {"title": "a", "body": "b"}, "refs": [{"id": 2, "x": 0}, {"id": 3}]}"#,
+        )
+        .unwrap();
+        let paths = [
+            "id",
+            "attributes.title",
+            "type",
+            "refs[*].id",
+            "missing.field",
+        ]
+        .map(path);
+        crate::processor::assert_json(
+            &project(&value, &paths),
+            r#"{"type":"t","id":1,"attributes":{"title":"a"},"refs":[{"id":2},{"id":3}]}"#,
+        );
+        assert_eq!(
+            project(&value, &[path("refs[1]")]),
+            json!({"refs": [{"id": 3}]})
+        );
+        assert_eq!(project(&value, &[path("nothing")]), json!({}));
+    }
+
+    #[test]
+    fn updates_every_match() {
+        let mut value = json!({"a": {"n": 1}, "b": {"n": 2}});
+        path("*.n").update(&mut value, |n| json!(n.as_i64().unwrap_or_default() * 10));
+        assert_eq!(value, json!({"a": {"n": 10}, "b": {"n": 20}}));
+    }
+}
@@ -0,0 +1,285 @@
+use super::{FieldPath, path::make_path};
+use eyre::{Report, Result, eyre};
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+
+/// Moves the value at one field path to another, like `_id=id` or `attributes.title=title`.
+///
+/// A key renamed within the same object keeps its position, otherwise the value is added
+/// at the end of its new parent, which is created if it doesn't exist.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Rename {
+    from: FieldPath,
+    to: FieldPath,
+    from_tokens: Vec<String>,
+    to_tokens: Vec<String>,
+}
+
+impl Rename {
+    pub fn apply(&self, value: &mut Value) -> Result<()> {
+        let (Some((from_key, from_parent)), Some((to_key, to_parent))) =
+            (self.from_tokens.split_last(), self.to_tokens.split_last())
+        else {
+            return Ok(());
+        };
+        let Some(parent) = get_mut(value, from_parent) else {
+            return Ok(());
+        };
+        if from_parent == to_parent
+            && let Value::Object(object) = parent
+        {
+            if from_key != to_key && object.contains_key(from_key) {
+                *object = std::mem::take(object)
+                    .into_iter()
+                    .filter(|(key, _)| key != to_key)
+                    .map(|(key, value)| match key == *from_key {
+                        true => (to_key.clone(), value),
+                        false => (key, value),
+                    })
+                    .collect();
+            }
+            return Ok(());
+        }
+        let Some(moved) = take(parent, from_key) else {
+            return Ok(());
+        };
+        let target = make_path(value, to_parent).map_err(|token| self.blocked(token))?;
+        match target {
+            Value::Object(object) => {
+                object.insert(to_key.clone(), moved);
+            }
+            Value::Array(array) => match to_key.parse::<usize>() {
+                Ok(index) if index <= array.len() => array.insert(index, moved),
+                _ => return Err(self.blocked(to_key)),
+            },
+            _ => return Err(self.blocked(to_key)),
+        }
+        Ok(())
+    }
+
+    fn blocked(&self, token: &str) -> Report {
+        eyre!(
+            "Can't rename `{}` to `{}`, there is no object to hold `{token}`",
+            self.from,
+            self.to
+        )
+    }
+}
+
+fn get_mut<'a>(value: &'a mut Value, tokens: &[String]) -> Option<&'a mut Value> {
+    tokens.iter().try_fold(value, |value, token| match value {
+        Value::Object(object) => object.get_mut(token),
+        Value::Array(array) => array.get_mut(token.parse::<usize>().ok()?),
+        _ => None,
+    })
+}
+
+/// Removes a key from an object or an element from an array, keeping the order of the rest
+fn take(parent: &mut Value, token: &str) -> Option<Value> {
+    match parent {
+        Value::Object(object) => {
+            let value = object.get(token)?.clone();
+            object.retain(|key, _| key != token);
+            Some(value)
+        }
+        Value::Array(array) => {
+            let index = token.parse::<usize>().ok().filter(|&i| i < array.len())?;
+            Some(array.remove(index))
+        }
+        _ => None,
+    }
+}
+
+impl std::str::FromStr for Rename {
+    type Err = Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (from, to) = s
+            .split_once('=')
+            .ok_or_else(|| eyre!("Rename `{s}` should look like `from=to`"))?;
+        let (from, to): (FieldPath, FieldPath) = (from.parse()?, to.parse()?);
+        Ok(Self {
+            from_tokens: from.tokens()?,
+            to_tokens: to.tokens()?,
+            from,
+            to,
+        })
+    }
+}
+
+/// Naming convention to convert every object key to
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KeyCase {
+    /// `snake_case`
+    Snake,
+    /// `camelCase`
+    Camel,
+    /// `kebab-case`
+    Kebab,
+}
+
+impl KeyCase {
+    /// Converts the keys of every object in `value`, at every level of nesting, failing when
+    /// two keys of the same object convert to the same name
+    pub fn apply(self, value: Value) -> Result<Value> {
+        match value {
+            Value::Object(object) => {
+                let mut converted = Map::with_capacity(object.len());
+                let mut originals = HashMap::with_capacity(object.len());
+                for (key, value) in object {
+                    let name = self.convert(&key);
+                    if let Some(earlier) = originals.insert(name.clone(), key.clone()) {
+                        return Err(eyre!(
+                            "Keys `{earlier}` and `{key}` both convert to `{name}`"
+                        ));
+                    }
+                    converted.insert(name, self.apply(value)?);
+                }
+                Ok(Value::Object(converted))
+            }
+            Value::Array(array) => array.into_iter().map(|value| self.apply(value)).collect(),
+            value => Ok(value),
+        }
+    }
+
+    /// Converts a single key. Leading `_` or `-`, like in `_id`, are kept as they are.
+    pub fn convert(self, key: &str) -> String {
+        let body = key.trim_start_matches(['_', '-']);
+        let prefix = &key[..key.len() - body.len()];
+        let words = words(body);
+        let converted = match self {
+            Self::Snake => words.join("_"),
+            Self::Kebab => words.join("-"),
+            Self::Camel => words
+                .iter()
+                .enumerate()
+                .map(|(i, word)| match i {
+                    0 => word.clone(),
+                    _ => capitalize(word),
+                })
+                .collect(),
+        };
+        format!("{prefix}{converted}")
+    }
+}
+
+/// Splits a key into lowercase words at separators and at case changes, so `HTTPServer_id`
+/// becomes `http`, `server` and `id`
+fn words(key: &str) -> Vec<String> {
+    let chars: Vec<char> = key.chars().collect();
+    let mut words = Vec::new();
+    let mut word = String::new();
+    for (i, &c) in chars.iter().enumerate() {
+        if matches!(c, '_' | '-' | ' ' | '.') {
+            words.push(std::mem::take(&mut word));
+            continue;
+        }
+        let previous = i.checked_sub(1).map(|i| chars[i]);
+        let next = chars.get(i + 1);
+        let boundary = c.is_uppercase()
+            && previous.is_some_and(|p| {
+                p.is_lowercase()
+                    || p.is_ascii_digit()
+                    || (p.is_uppercase() && next.is_some_and(|n| n.is_lowercase()))
+            });
+        if boundary {
+            words.push(std::mem::take(&mut word));
+        }
+        word.extend(c.to_lowercase());
+    }
+    words.push(word);
+    words.retain(|word| !word.is_empty());
+    words
+}
+
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+impl std::str::FromStr for KeyCase {
+    type Err = Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "snake" => Ok(Self::Snake),
+            "camel" => Ok(Self::Camel),
+            "kebab" => Ok(Self::Kebab),
+            s => Err(eyre!(
+                "Unknown key case `{s}`, expected snake, camel or kebab"
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::processor::assert_json;
+    use serde_json::json;
+
+    fn rename(value: &str, renames: &[&str]) -> Result<Value> {
+        let mut value: Value = serde_json::from_str(value)?;
+        for rename in renames {
+            rename.parse::<Rename>()?.apply(&mut value)?;
+        }
+        Ok(value)
+    }
+
+    #[test]
+    fn renames_in_place() -> Result<()> {
+        assert_json(
+            &rename(r#"{"a":1,"_id":2,"b":3}"#, &["_id=id"])?,
+            r#"{"a":1,"id":2,"b":3}"#,
+        );
+        assert_json(&rename(r#"{"a":1}"#, &["missing=id"])?, r#"{"a":1}"#);
+        Ok(())
+    }
+
+    #[test]
+    fn moves_between_paths() -> Result<()> {
+        assert_json(
+            &rename(
+                r#"{"attributes":{"title":"t","body":"b"},"id":1}"#,
+                &["attributes.title=title", "id=meta.ids[0]"],
+            )?,
+            r#"{"attributes":{"body":"b"},"title":"t","meta":{"ids":{"0":1}}}"#,
+        );
+        assert_json(
+            &rename(r#"{"a":[1,2],"b":3}"#, &["b=a[1]"])?,
+            r#"{"a":[1,3,2]}"#,
+        );
+        assert!(rename(r#"{"a":1,"b":2}"#, &["b=a.c"]).is_err());
+        assert!("a.*=b".parse::<Rename>().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn converts_key_case() {
+        let value =
+            json!({"_id": 1, "first_name": {"HTTPServer": 2, "kebab-key": [{"camelCase2": 3}]}});
+        assert_eq!(
+            KeyCase::Camel.apply(value.clone()).unwrap(),
+            json!({"_id": 1, "firstName": {"httpServer": 2, "kebabKey": [{"camelCase2": 3}]}})
+        );
+        assert_eq!(
+            KeyCase::Snake.apply(value.clone()).unwrap(),
+            json!({"_id": 1, "first_name": {"http_server": 2, "kebab_key": [{"camel_case2": 3}]}})
+        );
+        assert_eq!(KeyCase::Kebab.convert("firstName2Go"), "first-name2-go");
+    }
+
+    #[test]
+    fn rejects_keys_converting_to_the_same_name() {
+        let error = KeyCase::Snake
+            .apply(json!({"a": {"firstName": 1, "first_name": 2}}))
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Keys `firstName` and `first_name` both convert to `first_name`"
+        );
+    }
+}
@@ -0,0 +1,317 @@
+use crate::{filename, output::JsonFormat, processor::FieldPath};
+use eyre::{Report, Result, eyre};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+
+/// An output name built from a record, like `{type}/{attributes.title|slug}-{id}`.
+///
+/// Each `{...}` placeholder lists one or more comma-separated sources, the first one
+/// that has a value is used. A source is a field path, a `'quoted'` literal,
+/// or one of `@index`, `@key` and `@hash`. Commas, `|` and `}` inside quotes, regexes
+/// or after `\` don't end a source. Sources can be followed by `|` filters:
+/// `slug`, `lower`, `upper`, `pad:N` and `trunc:N`. Use `{{` and `}}` for literal braces.
+///
+/// Rendered values are encoded as filenames, so a `/` inside a field doesn't add a directory.
+#[derive(Clone, Debug, PartialEq)]
+pub struct NameTemplate {
+    parts: Vec<Part>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Part {
+    Literal(String),
+    Placeholder {
+        sources: Vec<Source>,
+        filters: Vec<Filter>,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Source {
+    /// Path to a field of the record, the first match is used
+    Field(FieldPath),
+    Literal(String),
+    /// Position of the record in the input
+    Index,
+    /// Key of the entry, or the default name of an unbundled record
+    Key,
+    /// SHA-256 of the canonical record, so it doesn't depend on key order
+    Hash,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Filter {
+    Slug,
+    Lower,
+    Upper,
+    Pad(usize),
+    Trunc(usize),
+}
+
+impl NameTemplate {
+    /// Renders the name of `value`, found at position `index` under `key`
+    pub fn render(&self, value: &Value, key: &str, index: usize) -> Result<String> {
+        let mut name = String::new();
+        for part in &self.parts {
+            match part {
+                Part::Literal(text) => name.push_str(text),
+                Part::Placeholder { sources, filters } => {
+                    let text = sources
+                        .iter()
+                        .find_map(|source| source.resolve(value, key, index).transpose())
+                        .transpose()?
+                        .ok_or_else(|| {
+                            eyre!("No value for `{{{}}}` in {key}", describe(sources))
+                        })?;
+                    let text = filters.iter().fold(text, |text, filter| filter.apply(text));
+                    // Values are encoded so only the template itself can add directories
+                    name.push_str(&filename::encode(&text));
+                }
+            }
+        }
+        // The `.json` extension is always added by the output
+        Ok(name.strip_suffix(".json").map(String::from).unwrap_or(name))
+    }
+}
+
+impl Source {
+    fn resolve(&self, value: &Value, key: &str, index: usize) -> Result<Option<String>> {
+        Ok(match self {
+            Self::Field(path) => path
+                .select(value)
+                .into_iter()
+                .find_map(|value| match value {
+                    Value::String(string) if !string.is_empty() => Some(string.clone()),
+                    Value::Number(number) => Some(number.to_string()),
+                    Value::Bool(bool) => Some(bool.to_string()),
+                    _ => None,
+                }),
+            Self::Literal(text) => Some(text.clone()),
+            Self::Index => Some(index.to_string()),
+            Self::Key => Some(key.to_string()),
+            Self::Hash => {
+                let digest = Sha256::digest(JsonFormat::canonical().to_string(value)?.as_bytes());
+                Some(format!("{digest:x}"))
+            }
+        })
+    }
+}
+
+impl Filter {
+    fn apply(&self, text: String) -> String {
+        match self {
+            Self::Slug => slug(&text),
+            Self::Lower => text.to_lowercase(),
+            Self::Upper => text.to_uppercase(),
+            Self::Pad(width) => format!("{text:0>width$}"),
+            Self::Trunc(width) => text.chars().take(*width).collect(),
+        }
+    }
+}
+
+/// Lowercases `text` and joins its alphanumeric runs with dashes
+fn slug(text: &str) -> String {
+    text.to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+fn describe(sources: &[Source]) -> String {
+    sources
+        .iter()
+        .map(|source| match source {
+            Source::Field(path) => path.to_string(),
+            Source::Literal(text) => format!("'{text}'"),
+            Source::Index => "@index".to_string(),
+            Source::Key => "@key".to_string(),
+            Source::Hash => "@hash".to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+impl std::str::FromStr for Source {
+    type Err = Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "@index" => Ok(Self::Index),
+            "@key" => Ok(Self::Key),
+            "@hash" => Ok(Self::Hash),
+            s if s.starts_with('@') => Err(eyre!("Unknown template variable `{s}`")),
+            s if s.len() >= 2 && s.starts_with('\'') && s.ends_with('\'') => {
+                Ok(Self::Literal(s[1..s.len() - 1].to_string()))
+            }
+            "" => Err(eyre!("Empty template field")),
+            s => Ok(Self::Field(s.parse()?)),
+        }
+    }
+}
+
+impl std::str::FromStr for Filter {
+    type Err = Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let width = |arg: Option<&str>| -> Result<usize> {
+            let arg = arg.ok_or_else(|| eyre!("Template filter `{s}` needs a width"))?;
+            Ok(arg.parse()?)
+        };
+        let (name, arg) = match s.split_once(':') {
+            Some((name, arg)) => (name, Some(arg)),
+            None => (s, None),
+        };
+        match name {
+            "slug" => Ok(Self::Slug),
+            "lower" => Ok(Self::Lower),
+            "upper" => Ok(Self::Upper),
+            "pad" => Ok(Self::Pad(width(arg)?)),
+            "trunc" => Ok(Self::Trunc(width(arg)?)),
+            name => Err(eyre!("Unknown template filter `{name}`")),
+        }
+    }
+}
+
+impl std::str::FromStr for NameTemplate {
+    type Err = Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut parts = Vec::new();
+        let mut literal = String::new();
+        let mut chars = s.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.as_str().starts_with('{') => {
+                    chars.next();
+                    literal.push('{');
+                }
+                '}' if chars.as_str().starts_with('}') => {
+                    chars.next();
+                    literal.push('}');
+                }
+                '{' => {
+                    let rest = chars.as_str();
+                    let end = find_unquoted(rest, '}')
+                        .ok_or_else(|| eyre!("Unclosed `{{` in name template `{s}`"))?;
+                    let mut pipeline = split_unquoted(&rest[..end], '|').into_iter().map(str::trim);
+                    let sources = split_unquoted(pipeline.next().unwrap_or_default(), ',')
+                        .into_iter()
+                        .map(|source| source.trim().parse())
+                        .collect::<Result<Vec<Source>>>()?;
+                    let filters = pipeline.map(str::parse).collect::<Result<Vec<Filter>>>()?;
+                    if !literal.is_empty() {
+                        parts.push(Part::Literal(std::mem::take(&mut literal)));
+                    }
+                    parts.push(Part::Placeholder { sources, filters });
+                    chars = rest[end + 1..].chars();
+                }
+                '}' => return Err(eyre!("Unmatched `}}` in name template `{s}`")),
+                c => literal.push(c),
+            }
+        }
+        if !literal.is_empty() {
+            parts.push(Part::Literal(literal));
+        }
+        Ok(Self { parts })
+    }
+}
+
+/// Finds the first `target` in a placeholder that isn't quoted, inside a `/regex/`
+/// or escaped with `\`, the same spans a field path reads as a single key
+fn find_unquoted(s: &str, target: char) -> Option<usize> {
+    let mut quote = None;
+    let mut escaped = false;
+    for (i, c) in s.char_indices() {
+        match quote {
+            _ if escaped => escaped = false,
+            _ if c == '\\' => escaped = true,
+            Some(end) if c == end => quote = None,
+            Some(_) => {}
+            None if c == target => return Some(i),
+            None if matches!(c, '\'' | '"' | '/') => quote = Some(c),
+            None => {}
+        }
+    }
+    None
+}
+
+/// Splits a placeholder at each `separator` that `find_unquoted` finds
+fn split_unquoted(s: &str, separator: char) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut rest = s;
+    while let Some(i) = find_unquoted(rest, separator) {
+        parts.push(&rest[..i]);
+        rest = &rest[i + separator.len_utf8()..];
+    }
+    parts.push(rest);
+    parts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn render(template: &str, value: &Value) -> Result<String> {
+        template.parse::<NameTemplate>()?.render(value, "alpha", 7)
+    }
+
+    #[test]
+    fn renders_nested_fields_and_filters() -> Result<()> {
+        let value =
+            json!({"type": "dashboard", "id": 42, "attributes": {"title": "Web Traffic (EU)"}});
+        assert_eq!(
+            render("{type}/{attributes.title|slug}-{id}.json", &value)?,
+            "dashboard/web-traffic-eu-42"
+        );
+        assert_eq!(render("{@index|pad:4}-{@key|upper}", &value)?, "0007-ALPHA");
+        Ok(())
+    }
+
+    #[test]
+    fn falls_back_to_next_source() -> Result<()> {
+        let value = json!({"name": null, "id": ""});
+        assert_eq!(render("{name,id,'untitled'}", &value)?, "untitled");
+        assert!(render("{name}", &value).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn keeps_quoted_separators() -> Result<()> {
+        let value = json!({"a,b": "comma", "c|d": "pipe", "id": 42});
+        assert_eq!(render(r#"{"a,b"}"#, &value)?, "comma");
+        assert_eq!(render(r#"{missing,'c|d'|upper}"#, &value)?, "C%7CD");
+        assert_eq!(render(r#"{"c|d"|upper}-{'a,b}'}"#, &value)?, "PIPE-a,b}");
+        assert_eq!(render(r"{a\,b}", &value)?, "comma");
+        assert_eq!(render("{/^i[d]{1,2}$/}", &value)?, "42");
+        Ok(())
+    }
+
+    #[test]
+    fn hashes_content() -> Result<()> {
+        let name = render("{@hash|trunc:12}", &json!({"alpha": 1}))?;
+        assert_eq!(name.len(), 12);
+        let reordered = serde_json::from_str(r#"{"bravo": 2.0, "alpha": 1}"#)?;
+        assert_eq!(
+            render("{@hash}", &json!({"alpha": 1, "bravo": 2}))?,
+            render("{@hash}", &reordered)?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn rejects_bad_templates() {
+        assert!("{type".parse::<NameTemplate>().is_err());
+        assert!("{type|shout}".parse::<NameTemplate>().is_err());
+        assert!("{@nope}".parse::<NameTemplate>().is_err());
+        assert_eq!(
+            "{{literal}}"
+                .parse::<NameTemplate>()
+                .unwrap()
+                .render(&json!({}), "", 0)
+                .unwrap(),
+            "{literal}"
+        );
+    }
+}
@@ -0,0 +1,112 @@
+use super::{Assignment, FieldPath, KeyCase, Origin, Pseudonymizer, Rename, json::Json};
+use eyre::Result;
+
+/// The field transforms every command applies to its records, set once from the command line.
+///
+/// Fields are kept, dropped, redacted, pseudonymized and renamed in that order, then keys are
+/// converted to `key_case` and the assignments are set last, so set fields are never touched by
+/// the other transforms.
+#[derive(Clone, Default)]
+pub struct Transforms {
+    keep: Option<Vec<FieldPath>>,
+    drop: Option<Vec<FieldPath>>,
+    redact: Option<Vec<FieldPath>>,
+    pseudonymizer: Option<Pseudonymizer>,
+    renames: Option<Vec<Rename>>,
+    key_case: Option<KeyCase>,
+    assignments: Option<Vec<Assignment>>,
+}
+
+impl Transforms {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Keep only these fields, dropping everything else
+    pub fn keep(mut self, fields: Option<Vec<FieldPath>>) -> Self {
+        self.keep = fields;
+        self
+    }
+
+    pub fn drop(mut self, fields: Option<Vec<FieldPath>>) -> Self {
+        self.drop = fields;
+        self
+    }
+
+    pub fn redact(mut self, fields: Option<Vec<FieldPath>>) -> Self {
+        self.redact = fields;
+        self
+    }
+
+    pub fn pseudonymizer(mut self, pseudonymizer: Option<Pseudonymizer>) -> Self {
+        self.pseudonymizer = pseudonymizer;
+        self
+    }
+
+    pub fn renames(mut self, renames: Option<Vec<Rename>>) -> Self {
+        self.renames = renames;
+        self
+    }
+
+    pub fn key_case(mut self, case: Option<KeyCase>) -> Self {
+        self.key_case = case;
+        self
+    }
+
+    /// Fields to set on every record, after the other transforms
+    pub fn assignments(mut self, assignments: Option<Vec<Assignment>>) -> Self {
+        self.assignments = assignments;
+        self
+    }
+
+    /// Runs every transform on a record read from `origin`
+    pub fn apply(&self, json: Json, origin: &Origin) -> Result<Json> {
+        self.convert(self.select(json)?, origin)
+    }
+
+    /// Keeps, drops, redacts, pseudonymizes and renames fields
+    pub fn select(&self, json: Json) -> Result<Json> {
+        json.keep(self.keep.as_ref())
+            .drop(self.drop.as_ref())
+            .redact(self.redact.as_ref())
+            .pseudonymize(self.pseudonymizer.as_ref())?
+            .rename(self.renames.as_ref())
+    }
+
+    /// Converts the case of keys, then sets fields. Merged objects only convert the values
+    /// of their entries, since entry names come from filenames
+    pub fn convert(&self, json: Json, origin: &Origin) -> Result<Json> {
+        json.key_case(self.key_case)?
+            .set(self.assignments.as_ref(), origin)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::processor::assert_json;
+    use serde_json::json;
+
+    #[test]
+    fn sets_fields_after_the_other_transforms() -> Result<()> {
+        let transforms = Transforms::new()
+            .drop(Some(vec!["secret".parse()?]))
+            .renames(Some(vec!["user_name=userName".parse()?]))
+            .key_case(Some("snake".parse()?))
+            .assignments(Some(vec![Assignment::constant("setBy=\"jsrmx\"")?]));
+        let origin = Origin {
+            filename: None,
+            index: 0,
+            key: None,
+        };
+        let json = transforms.apply(
+            Json::from(json!({"user_name": "a", "secret": 1, "firstName": "b"})),
+            &origin,
+        )?;
+        assert_json(
+            &json.value,
+            r#"{"user_name":"a","first_name":"b","setBy":"jsrmx"}"#,
+        );
+        Ok(())
+    }
+}
@@ -155,6 +155,143 @@ fn bundle_command_stdout() -> std::io::Result<()> {
     Ok(())
 }
 
+#[test]
+fn bundle_command_compressed_inputs() -> std::io::Result<()> {
+    use std::io::Write;
+
+    let input_dir = tempdir()?;
+    let alpha = json!({"name": "alpha", "position": 1});
+    let bravo = json!({"name": "bravo", "position": 2});
+    let charlie = json!({"name": "charlie", "position": 3});
+
+    let mut gzip = flate2::write::GzEncoder::new(
+        fs::File::create(input_dir.path().join("alpha.json.gz"))?,
+        flate2::Compression::default(),
+    );
+    gzip.write_all(alpha.to_string().as_bytes())?;
+    gzip.finish()?;
+    fs::write(
+        input_dir.path().join("bravo.json.zst"),
+        zstd::encode_all(bravo.to_string().as_bytes(), 0)?,
+    )?;
+    let mut bzip = bzip2::write::BzEncoder::new(
+        fs::File::create(input_dir.path().join("charlie.json.bz2"))?,
+        bzip2::Compression::default(),
+    );
+    bzip.write_all(charlie.to_string().as_bytes())?;
+    bzip.finish()?;
+
+    let output = Command::cargo_bin("jsrmx")
+        .unwrap()
+        .arg("bundle")
+        .arg(input_dir.path())
+        .arg("-")
+        .output()?;
+
+    assert!(
+        output.status.success(),
+        "Bundle command failed: {:?}",
+        output
+    );
+
+    let stdout_content = String::from_utf8_lossy(&output.stdout);
+    let output_lines: Vec<serde_json::Value> = stdout_content
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+
+    assert_eq!(output_lines.len(), 3);
+    for expected in [&alpha, &bravo, &charlie] {
+        assert!(
+            output_lines.contains(expected),
+            "Expected item not found in output: {:?}",
+            expected
+        );
+    }
+
+    Ok(())
+}
+
+#[test]
+fn bundle_command_compressed_output() -> std::io::Result<()> {
+    use std::io::Read;
+
+    let input_dir = tempdir()?;
+    let output_dir = tempdir()?;
+    create_input_files(&input_dir)?;
+
+    let output_file = output_dir.path().join("letters.ndjson.gz");
+
+    let output = Command::cargo_bin("jsrmx")
+        .unwrap()
+        .arg("bundle")
+        .arg(input_dir.path())
+        .arg(&output_file)
+        .output()?;
+
+    assert!(
+        output.status.success(),
+        "Bundle command failed: {:?}",
+        output
+    );
+
+    let mut output_content = String::new();
+    flate2::read::GzDecoder::new(fs::File::open(&output_file)?)
+        .read_to_string(&mut output_content)?;
+    assert_eq!(output_content.lines().count(), 6);
+    for line in output_content.lines() {
+        let _: serde_json::Value = serde_json::from_str(line)?;
+    }
+
+    Ok(())
+}
+
+#[test]
+fn bundle_command_compress_overrides_extension() -> std::io::Result<()> {
+    let input_dir = tempdir()?;
+    let output_dir = tempdir()?;
+    let output_file = output_dir.path().join("empty.ndjson.gz");
+
+    Command::cargo_bin("jsrmx")
+        .unwrap()
+        .args(["bundle", "--compress", "zstd"])
+        .arg(input_dir.path())
+        .arg(&output_file)
+        .assert()
+        .success();
+    assert!(zstd::decode_all(fs::File::open(&output_file)?)?.is_empty());
+
+    create_input_files(&input_dir)?;
+    Command::cargo_bin("jsrmx")
+        .unwrap()
+        .args(["bundle", "--compress", "zstd"])
+        .arg(input_dir.path())
+        .arg(&output_file)
+        .assert()
+        .success();
+    let content = zstd::decode_all(fs::File::open(&output_file)?)?;
+    assert_eq!(String::from_utf8_lossy(&content).lines().count(), 6);
+
+    Ok(())
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn bundle_command_reports_compressed_write_errors() -> std::io::Result<()> {
+    let input_dir = tempdir()?;
+    create_input_files(&input_dir)?;
+
+    // The whole zstd stream stays in the encoder until it is finished, so only that can fail
+    let status = std::process::Command::new(assert_cmd::cargo::cargo_bin("jsrmx"))
+        .args(["bundle", "--compress", "zstd"])
+        .arg(input_dir.path())
+        .stdout(fs::OpenOptions::new().write(true).open("/dev/full")?)
+        .status()?;
+    assert!(!status.success());
+
+    Ok(())
+}
+
 #[test]
 fn bundle_command_with_drop() -> std::io::Result<()> {
     let input_dir = tempdir()?;
@@ -210,3 +347,358 @@ fn bundle_command_with_drop() -> std::io::Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn bundle_command_from_archive() -> std::io::Result<()> {
+    let input_dir = tempdir()?;
+    create_input_files(&input_dir)?;
+    let archive_dir = tempdir()?;
+    let archive = archive_dir.path().join("letters.tar");
+    let mut builder = tar::Builder::new(fs::File::create(&archive)?);
+    builder.append_dir_all(".", input_dir.path())?;
+    builder.finish()?;
+
+    let output = Command::cargo_bin("jsrmx")
+        .unwrap()
+        .arg("bundle")
+        .arg(&archive)
+        .arg("-")
+        .output()?;
+    assert!(
+        output.status.success(),
+        "Bundle command failed: {:?}",
+        output
+    );
+
+    let mut positions = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap()["position"].clone())
+        .collect::<Vec<_>>();
+    positions.sort_by_key(|position| position.as_u64());
+    assert_eq!(positions, [1, 2, 3, 4, 5, 6]);
+
+    Ok(())
+}
+
+#[test]
+fn bundle_command_fails_on_corrupt_archive() -> std::io::Result<()> {
+    let archive_dir = tempdir()?;
+    let archive = archive_dir.path().join("corrupt.tar");
+    fs::write(&archive, [b'x'; 1024])?;
+
+    let output = Command::cargo_bin("jsrmx")
+        .unwrap()
+        .arg("bundle")
+        .arg(&archive)
+        .arg("-")
+        .output()?;
+    assert_eq!(output.status.code(), Some(1), "{output:?}");
+    assert!(output.stdout.is_empty());
+
+    Ok(())
+}
+
+#[test]
+fn bundle_command_skips_hidden_files() -> std::io::Result<()> {
+    let input_dir = tempdir()?;
+    create_input_files(&input_dir)?;
+    fs::write(
+        input_dir.path().join(".hidden.json"),
+        r#"{"name":"hidden"}"#,
+    )?;
+
+    let output = Command::cargo_bin("jsrmx")
+        .unwrap()
+        .arg("bundle")
+        .arg("--strict")
+        .arg(input_dir.path())
+        .arg("-")
+        .output()?;
+    assert!(
+        output.status.success(),
+        "Bundle command failed: {:?}",
+        output
+    );
+    assert_eq!(String::from_utf8_lossy(&output.stdout).lines().count(), 6);
+
+    let output = Command::cargo_bin("jsrmx")
+        .unwrap()
+        .arg("bundle")
+        .arg("--hidden")
+        .arg(input_dir.path())
+        .arg("-")
+        .output()?;
+    assert!(
+        output.status.success(),
+        "Bundle command failed: {:?}",
+        output
+    );
+    assert_eq!(String::from_utf8_lossy(&output.stdout).lines().count(), 7);
+
+    Ok(())
+}
+
+fn create_object_tree(dir: &tempfile::TempDir) -> std::io::Result<()> {
+    for (path, content) in [
+        ("dashboard/1.json", json!({"type": "dashboard", "id": 1})),
+        ("dashboard/2.json", json!({"type": "dashboard", "id": 2})),
+        ("search/3.json", json!({"type": "search", "id": 3})),
+    ] {
+        let file_path = dir.path().join(path);
+        fs::create_dir_all(file_path.parent().unwrap())?;
+        fs::write(file_path, content.to_string())?;
+    }
+    Ok(())
+}
+
+fn bundled_ids(output: &std::process::Output) -> Vec<u64> {
+    let mut ids = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(|line| {
+            serde_json::from_str::<serde_json::Value>(line).unwrap()["id"]
+                .as_u64()
+                .unwrap()
+        })
+        .collect::<Vec<_>>();
+    ids.sort();
+    ids
+}
+
+#[test]
+fn bundle_command_recursive() -> std::io::Result<()> {
+    let input_dir = tempdir()?;
+    create_object_tree(&input_dir)?;
+
+    let output = Command::cargo_bin("jsrmx")
+        .unwrap()
+        .arg("bundle")
+        .arg("--recursive")
+        .arg(input_dir.path())
+        .arg("-")
+        .output()?;
+    assert!(
+        output.status.success(),
+        "Bundle command failed: {:?}",
+        output
+    );
+    assert_eq!(bundled_ids(&output), [1, 2, 3]);
+
+    // Globs select a slice of the tree by relative path
+    let output = Command::cargo_bin("jsrmx")
+        .unwrap()
+        .arg("bundle")
+        .arg("--recursive")
+        .arg("--include=dashboard/*.json")
+        .arg("--exclude=**/2.json")
+        .arg(input_dir.path())
+        .arg("-")
+        .output()?;
+    assert!(
+        output.status.success(),
+        "Bundle command failed: {:?}",
+        output
+    );
+    assert_eq!(bundled_ids(&output), [1]);
+
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn bundle_command_symlinks() -> std::io::Result<()> {
+    let input_dir = tempdir()?;
+    create_object_tree(&input_dir)?;
+    let linked_dir = tempdir()?;
+    fs::write(
+        linked_dir.path().join("4.json"),
+        json!({"id": 4}).to_string(),
+    )?;
+    std::os::unix::fs::symlink(linked_dir.path(), input_dir.path().join("linked"))?;
+    // A link back to the root must not be walked forever
+    std::os::unix::fs::symlink(input_dir.path(), input_dir.path().join("search/loop"))?;
+
+    let output = Command::cargo_bin("jsrmx")
+        .unwrap()
+        .arg("bundle")
+        .arg("--recursive")
+        .arg(input_dir.path())
+        .arg("-")
+        .output()?;
+    assert!(
+        output.status.success(),
+        "Bundle command failed: {:?}",
+        output
+    );
+    assert_eq!(bundled_ids(&output), [1, 2, 3, 4]);
+
+    let output = Command::cargo_bin("jsrmx")
+        .unwrap()
+        .arg("bundle")
+        .arg("--recursive")
+        .arg("--no-follow-symlinks")
+        .arg(input_dir.path())
+        .arg("-")
+        .output()?;
+    assert!(
+        output.status.success(),
+        "Bundle command failed: {:?}",
+        output
+    );
+    assert_eq!(bundled_ids(&output), [1, 2, 3]);
+
+    Ok(())
+}
+
+#[test]
+fn bundle_rename_and_key_case() -> std::io::Result<()> {
+    let input_dir = tempdir()?;
+    fs::write(
+        input_dir.path().join("alpha.json"),
+        r#"{"_id":"a","first_name":"Alpha","attributes":{"title":"t","created_at":1}}"#,
+    )?;
+
+    let output = Command::cargo_bin("jsrmx")
+        .unwrap()
+        .arg("bundle")
+        .arg(input_dir.path())
+        .args(["--rename=_id=id,attributes.title=title", "--key-case=camel"])
+        .output()?;
+
+    assert!(output.status.success(), "{output:?}");
+    let record = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(
+        serde_json::from_str::<serde_json::Value>(&record)?,
+        json!({"id": "a", "firstName": "Alpha", "attributes": {"createdAt": 1}, "title": "t"})
+    );
+    #[cfg(feature = "preserve_order")]
+    assert_eq!(
+        record,
+        "{\"id\":\"a\",\"firstName\":\"Alpha\",\"attributes\":{\"createdAt\":1},\"title\":\"t\"}\n"
+    );
+
+    let output = Command::cargo_bin("jsrmx")
+        .unwrap()
+        .arg("bundle")
+        .arg(input_dir.path())
+        .arg("--rename=first_name=attributes.title.text")
+        .output()?;
+    assert!(!output.status.success());
+
+    // Keys that convert to the same name fail instead of one silently replacing the other
+    fs::write(
+        input_dir.path().join("alpha.json"),
+        r#"{"firstName":"Alpha","first_name":"Other"}"#,
+    )?;
+    let output = Command::cargo_bin("jsrmx")
+        .unwrap()
+        .arg("bundle")
+        .arg(input_dir.path())
+        .arg("--key-case=snake")
+        .output()?;
+    assert!(!output.status.success());
+    assert!(
+        String::from_utf8_lossy(&output.stderr)
+            .contains("Keys `firstName` and `first_name` both convert to `first_name`")
+    );
+
+    Ok(())
+}
+
+#[test]
+fn bundle_set_fields() -> std::io::Result<()> {
+    let input_dir = tempdir()?;
+    fs::write(input_dir.path().join("alpha.json"), r#"{"name":"a"}"#)?;
+    fs::write(input_dir.path().join("bravo.json"), r#"{"name":"b"}"#)?;
+
+    let output = Command::cargo_bin("jsrmx")
+        .unwrap()
+        .arg("bundle")
+        .arg(input_dir.path())
+        .args(["--set=migrationVersion=3", r#"--set=meta.tags=["x","y,z"]"#])
+        .args(["--set-from=_file=filename,_index=index"])
+        .output()?;
+
+    assert!(output.status.success(), "{output:?}");
+    let records: Vec<serde_json::Value> = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(serde_json::from_str)
+        .collect::<Result<_, _>>()?;
+    assert_eq!(
+        records,
+        [
+            json!({"name": "a", "migrationVersion": 3, "meta": {"tags": ["x", "y,z"]}, "_file": "alpha.json", "_index": 0}),
+            json!({"name": "b", "migrationVersion": 3, "meta": {"tags": ["x", "y,z"]}, "_file": "bravo.json", "_index": 1}),
+        ]
+    );
+    #[cfg(feature = "preserve_order")]
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        concat!(
+            r#"{"name":"a","migrationVersion":3,"meta":{"tags":["x","y,z"]},"_file":"alpha.json","_index":0}"#,
+            "\n",
+            r#"{"name":"b","migrationVersion":3,"meta":{"tags":["x","y,z"]},"_file":"bravo.json","_index":1}"#,
+            "\n",
+        )
+    );
+
+    Ok(())
+}
+
+#[test]
+fn bundle_set_from_filename() -> std::io::Result<()> {
+    use std::io::Write;
+
+    let input_dir = tempdir()?;
+    fs::create_dir(input_dir.path().join("sub"))?;
+    fs::write(input_dir.path().join("alpha.json"), r#"{"id":1}"#)?;
+    let mut encoder = flate2::write::GzEncoder::new(
+        fs::File::create(input_dir.path().join("sub/bravo.json.gz"))?,
+        Default::default(),
+    );
+    encoder.write_all(br#"{"id":2}"#)?;
+    encoder.finish()?;
+
+    let output = Command::cargo_bin("jsrmx")
+        .unwrap()
+        .args([
+            "bundle",
+            "--recursive",
+            "--set-from=_file=filename,_key=key",
+        ])
+        .arg(input_dir.path())
+        .output()?;
+
+    assert!(output.status.success(), "{output:?}");
+    let mut records: Vec<serde_json::Value> = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(serde_json::from_str)
+        .collect::<Result<_, _>>()?;
+    records.sort_by_key(|record| record["id"].as_u64());
+    assert_eq!(
+        records,
+        [
+            json!({"id": 1, "_file": "alpha.json", "_key": "alpha"}),
+            json!({"id": 2, "_file": "sub/bravo.json.gz", "_key": "sub/bravo"}),
+        ]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn bundle_strict_fails_on_unreadable_files() -> std::io::Result<()> {
+    let input_dir = tempdir()?;
+    create_input_files(&input_dir)?;
+    fs::write(input_dir.path().join("README.md"), "# Letters")?;
+
+    let output = Command::cargo_bin("jsrmx")
+        .unwrap()
+        .args(["bundle", "--strict"])
+        .arg(input_dir.path())
+        .output()?;
+
+    assert_eq!(output.status.code(), Some(1), "{output:?}");
+    assert!(String::from_utf8_lossy(&output.stderr).contains("README.md"));
+
+    Ok(())
+}
@@ -41,6 +41,204 @@ fn merge_basic() -> std::io::Result<()> {
     Ok(())
 }
 
+#[test]
+fn merge_tar_gz_archive() -> std::io::Result<()> {
+    let (input_dir, output_dir, files) = setup_merge_test()?;
+    let archive = output_dir.path().join("letters.tar.gz");
+
+    // Members under a leading directory, the way CI artifacts are usually packed
+    let encoder = flate2::write::GzEncoder::new(fs::File::create(&archive)?, Default::default());
+    let mut builder = tar::Builder::new(encoder);
+    builder.append_dir_all("./letters", input_dir.path())?;
+    builder.into_inner()?.finish()?;
+
+    let output = Command::cargo_bin("jsrmx")
+        .unwrap()
+        .arg("merge")
+        .arg("--recursive")
+        .arg(&archive)
+        .arg("-")
+        .output()?;
+    assert!(
+        output.status.success(),
+        "Archive merge failed: {:?}",
+        output
+    );
+
+    let merged: serde_json::Value = serde_json::from_slice(&output.stdout)?;
+    let letters = files
+        .into_iter()
+        .map(|(filename, value)| (filename.trim_end_matches(".json").to_string(), value))
+        .collect::<serde_json::Map<_, _>>();
+    assert_eq!(merged, json!({"letters": letters}));
+
+    Ok(())
+}
+
+#[test]
+fn merge_zip_archive_by_member_path() -> std::io::Result<()> {
+    use std::io::Write;
+
+    let output_dir = tempdir()?;
+    let archive = output_dir.path().join("letters.zip");
+    let mut zip = zip::ZipWriter::new(fs::File::create(&archive)?);
+    for (name, value) in [("build/alpha.json", json!(1)), ("bravo.json", json!(2))] {
+        zip.start_file(name, zip::write::SimpleFileOptions::default())?;
+        zip.write_all(value.to_string().as_bytes())?;
+    }
+    zip.finish()?;
+
+    let output = Command::cargo_bin("jsrmx")
+        .unwrap()
+        .arg("merge")
+        .arg(&archive)
+        .arg("-")
+        .output()?;
+    assert!(
+        output.status.success(),
+        "Archive merge failed: {:?}",
+        output
+    );
+
+    let merged: serde_json::Value = serde_json::from_slice(&output.stdout)?;
+    assert_eq!(merged, json!({"build/alpha": 1, "bravo": 2}));
+
+    Ok(())
+}
+
+#[test]
+fn merge_skips_non_json_files() -> std::io::Result<()> {
+    let (input_dir, _, _) = setup_merge_test()?;
+    fs::write(input_dir.path().join("README.md"), "# Letters")?;
+    fs::write(input_dir.path().join(".DS_Store"), [0u8, 1, 2])?;
+    fs::create_dir(input_dir.path().join("drafts"))?;
+    fs::write(input_dir.path().join(".jsrmxignore"), "echo.json\nfox*\n")?;
+
+    let output = Command::cargo_bin("jsrmx")
+        .unwrap()
+        .arg("merge")
+        .arg("--exclude=delta.json")
+        .arg(input_dir.path())
+        .arg("-")
+        .output()?;
+    assert!(output.status.success(), "Merge failed: {:?}", output);
+
+    let merged: serde_json::Value = serde_json::from_slice(&output.stdout)?;
+    let mut keys = merged.as_object().unwrap().keys().collect::<Vec<_>>();
+    keys.sort();
+    assert_eq!(keys, ["alpha", "bravo", "charlie"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("README.md"), "{stderr}");
+    assert!(stderr.contains("Skipped 1 unreadable files"), "{stderr}");
+
+    // Strict mode still fails on the first file that isn't JSON
+    let _ = Command::cargo_bin("jsrmx")
+        .unwrap()
+        .arg("merge")
+        .arg("--strict")
+        .arg(input_dir.path())
+        .arg("-")
+        .assert()
+        .failure();
+
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn merge_skips_non_utf8_filenames() -> std::io::Result<()> {
+    use std::os::unix::ffi::OsStrExt;
+
+    let (input_dir, _, _) = setup_merge_test()?;
+    let name = std::ffi::OsStr::from_bytes(b"caf\xe9.json");
+    fs::write(input_dir.path().join(name), "{}")?;
+
+    let output = Command::cargo_bin("jsrmx")
+        .unwrap()
+        .args(["merge", "--recursive"])
+        .arg(input_dir.path())
+        .arg("-")
+        .output()?;
+    assert!(output.status.success(), "Merge failed: {:?}", output);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("name isn't valid UTF-8"), "{stderr}");
+    assert!(stderr.contains("Skipped 1 unreadable files"), "{stderr}");
+
+    let output = Command::cargo_bin("jsrmx")
+        .unwrap()
+        .args(["merge", "--strict"])
+        .arg(input_dir.path())
+        .arg("-")
+        .output()?;
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("isn't valid UTF-8"));
+
+    Ok(())
+}
+
+#[test]
+fn merge_rejects_duplicate_names() -> std::io::Result<()> {
+    let merge = |input: &std::path::Path, recursive: bool| {
+        let mut command = Command::cargo_bin("jsrmx").unwrap();
+        command.arg("merge");
+        if recursive {
+            command.arg("--recursive");
+        }
+        command.arg(input).arg("-").output()
+    };
+
+    // The same stem with different extensions
+    let input_dir = tempdir()?;
+    fs::write(input_dir.path().join("alpha.json"), "1")?;
+    let mut gzip = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+    std::io::Write::write_all(&mut gzip, b"2")?;
+    fs::write(input_dir.path().join("alpha.json.gz"), gzip.finish()?)?;
+    let output = merge(input_dir.path(), false)?;
+    assert!(!output.status.success(), "{output:?}");
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Entry `alpha`"));
+
+    // A file and a subdirectory with the same name
+    let input_dir = tempdir()?;
+    fs::create_dir(input_dir.path().join("alpha"))?;
+    fs::write(input_dir.path().join("alpha/bravo.json"), "1")?;
+    fs::write(input_dir.path().join("alpha.json"), "2")?;
+    assert!(merge(input_dir.path(), false)?.status.success());
+    let output = merge(input_dir.path(), true)?;
+    assert!(!output.status.success(), "{output:?}");
+
+    // The same stem inside a subdirectory
+    let input_dir = tempdir()?;
+    fs::create_dir(input_dir.path().join("alpha"))?;
+    fs::write(input_dir.path().join("alpha/bravo.json"), "1")?;
+    fs::write(input_dir.path().join("alpha/bravo.ndjson"), "2")?;
+    let output = merge(input_dir.path(), true)?;
+    assert!(!output.status.success(), "{output:?}");
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Entry `bravo`"));
+
+    Ok(())
+}
+
+#[test]
+fn merge_include_glob() -> std::io::Result<()> {
+    let (input_dir, _, _) = setup_merge_test()?;
+
+    let output = Command::cargo_bin("jsrmx")
+        .unwrap()
+        .arg("merge")
+        .arg("--strict")
+        .arg("--include=a*.json,b*.json")
+        .arg(input_dir.path())
+        .arg("-")
+        .output()?;
+    assert!(output.status.success(), "Merge failed: {:?}", output);
+
+    let merged: serde_json::Value = serde_json::from_slice(&output.stdout)?;
+    assert_eq!(merged.as_object().unwrap().len(), 2);
+    assert!(merged.get("alpha").is_some() && merged.get("bravo").is_some());
+
+    Ok(())
+}
+
 // TODO: The --compact option is not yet implemented for the merge command
 // #[test]
 // fn merge_compact() -> std::io::Result<()> {
@@ -144,6 +342,120 @@ fn merge_with_drop() -> std::io::Result<()> {
     Ok(())
 }
 
+#[test]
+#[cfg(not(feature = "preserve_order"))]
+fn merge_sorted_compact() -> std::io::Result<()> {
+    let (input_dir, _, _) = setup_merge_test()?;
+
+    let output = Command::cargo_bin("jsrmx")
+        .unwrap()
+        .arg("merge")
+        .arg("--compact")
+        .arg("--sort")
+        .arg("--filter=alpha|bravo")
+        .arg(input_dir.path())
+        .arg("-")
+        .output()?;
+
+    assert!(
+        output.status.success(),
+        "Merge command with --sort failed: {:?}",
+        output
+    );
+
+    let expected = concat!(
+        r#"{"alpha":{"letter":{"lowercase":"a","uppercase":"A"},"position":1},"#,
+        r#""bravo":{"letter":{"lowercase":"b","uppercase":"B"},"position":2}}"#,
+        "\n"
+    );
+    assert_eq!(String::from_utf8_lossy(&output.stdout), expected);
+
+    Ok(())
+}
+
+#[test]
+#[cfg(feature = "preserve_order")]
+fn merge_sorted_compact_keeps_record_key_order() -> std::io::Result<()> {
+    let (input_dir, _, _) = setup_merge_test()?;
+
+    let output = Command::cargo_bin("jsrmx")
+        .unwrap()
+        .arg("merge")
+        .arg("--compact")
+        .arg("--sort")
+        .arg("--filter=alpha|bravo")
+        .arg(input_dir.path())
+        .arg("-")
+        .output()?;
+
+    assert!(
+        output.status.success(),
+        "Merge command with --sort failed: {:?}",
+        output
+    );
+
+    // `--sort` orders the merged entries, the keys inside each record keep their file order
+    let expected = concat!(
+        r#"{"alpha":{"letter":{"uppercase":"A","lowercase":"a"},"position":1},"#,
+        r#""bravo":{"letter":{"uppercase":"B","lowercase":"b"},"position":2}}"#,
+        "\n"
+    );
+    assert_eq!(String::from_utf8_lossy(&output.stdout), expected);
+
+    Ok(())
+}
+
+#[test]
+fn merge_key_order() -> std::io::Result<()> {
+    let (input_dir, _, _) = setup_merge_test()?;
+    fs::write(input_dir.path().join("item10.json"), "{}")?;
+    fs::write(input_dir.path().join("item2.json"), "{}")?;
+
+    let output = Command::cargo_bin("jsrmx")
+        .unwrap()
+        .args(["merge", "--compact", "--filter=alpha|item"])
+        .args(["--sort-keys=natural", "--key-order=position"])
+        .arg(input_dir.path())
+        .arg("-")
+        .output()?;
+
+    assert!(output.status.success(), "{output:?}");
+    let expected = concat!(
+        r#"{"alpha":{"position":1,"letter":{"lowercase":"a","uppercase":"A"}},"#,
+        r#""item2":{},"item10":{}}"#,
+        "\n"
+    );
+    assert_eq!(String::from_utf8_lossy(&output.stdout), expected);
+
+    Ok(())
+}
+
+#[test]
+fn merge_keep_fields() -> std::io::Result<()> {
+    let (input_dir, _, _) = setup_merge_test()?;
+
+    let output = Command::cargo_bin("jsrmx")
+        .unwrap()
+        .args(["merge", "--compact", "--sort", "--filter=alpha|bravo"])
+        .args([
+            "--keep=*.letter,alpha.position",
+            "--drop=*.letter.lowercase",
+        ])
+        .arg(input_dir.path())
+        .arg("-")
+        .output()?;
+
+    assert!(output.status.success(), "{output:?}");
+    let expected = concat!(
+        r#"{"alpha":{"letter":{"uppercase":"A"},"position":1},"#,
+        r#""bravo":{"letter":{"uppercase":"B"}}}"#,
+        "\n"
+    );
+    assert_eq!(String::from_utf8_lossy(&output.stdout), expected);
+
+    Ok(())
+}
+
 // TODO: The --trim option is not yet implemented for the merge command
 // #[test]
 // fn merge_trim() -> std::io::Result<()> {
@@ -230,3 +542,79 @@ fn setup_merge_test() -> std::io::Result<MergeTestSetup> {
 
     Ok((input_dir, output_dir, files))
 }
+
+#[test]
+fn merge_sorts_mixed_names_naturally() -> std::io::Result<()> {
+    let input_dir = tempdir()?;
+    let names = ["b", "a10", "10", "a2", "2", "a", "1", "a1", "01"];
+    for name in names {
+        fs::write(
+            input_dir.path().join(format!("{name}.json")),
+            format!("\"{name}\""),
+        )?;
+    }
+
+    let output = Command::cargo_bin("jsrmx")
+        .unwrap()
+        .args(["merge", "--compact", "--array"])
+        .arg(input_dir.path())
+        .arg("-")
+        .output()?;
+
+    assert!(output.status.success(), "{output:?}");
+    let merged: serde_json::Value = serde_json::from_slice(&output.stdout)?;
+    assert_eq!(
+        merged,
+        json!(["1", "01", "2", "10", "a", "a1", "a2", "a10", "b"])
+    );
+
+    Ok(())
+}
+
+#[test]
+#[cfg(feature = "preserve_order")]
+fn merge_reads_files_in_sorted_order_by_default() -> std::io::Result<()> {
+    let input_dir = tempdir()?;
+    for name in ["delta", "charlie", "bravo", "alpha", "echo"] {
+        fs::write(input_dir.path().join(format!("{name}.json")), "1")?;
+    }
+
+    let output = Command::cargo_bin("jsrmx")
+        .unwrap()
+        .args(["merge", "--compact"])
+        .arg(input_dir.path())
+        .arg("-")
+        .output()?;
+
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "{\"alpha\":1,\"bravo\":1,\"charlie\":1,\"delta\":1,\"echo\":1}\n"
+    );
+
+    Ok(())
+}
+
+#[test]
+#[cfg(feature = "preserve_order")]
+fn merge_sorts_object_keys_by_bytes() -> std::io::Result<()> {
+    let input_dir = tempdir()?;
+    for name in ["item2", "item10", "Item3"] {
+        fs::write(input_dir.path().join(format!("{name}.json")), "1")?;
+    }
+
+    let output = Command::cargo_bin("jsrmx")
+        .unwrap()
+        .args(["merge", "--compact", "--sort"])
+        .arg(input_dir.path())
+        .arg("-")
+        .output()?;
+
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "{\"Item3\":1,\"item10\":1,\"item2\":1}\n"
+    );
+
+    Ok(())
+}
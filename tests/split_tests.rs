@@ -119,6 +119,533 @@ fn split_with_drop() -> std::io::Result<()> {
     Ok(())
 }
 
+#[test]
+fn split_streams_filtered_entries() -> std::io::Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    let input_file = temp_dir.path().join("input.json");
+    let output_dir = temp_dir.path().join("output");
+
+    let input_json = json!({
+        "alpha": {"uppercase": "A", "lowercase": "a", "position": 1},
+        "bravo": {"uppercase": "B", "lowercase": "b", "position": 2},
+        "charlie": {"uppercase": "C", "lowercase": "c", "position": 3}
+    });
+    std::fs::write(&input_file, serde_json::to_string_pretty(&input_json)?)?;
+
+    let _ = Command::cargo_bin("jsrmx")
+        .unwrap()
+        .arg("split")
+        .arg("--filter=^(alpha|charlie)$")
+        .arg(&input_file)
+        .arg(&output_dir)
+        .assert()
+        .success();
+
+    assert!(output_dir.join("alpha.json").exists());
+    assert!(!output_dir.join("bravo.json").exists());
+    assert!(output_dir.join("charlie.json").exists());
+
+    let charlie_content: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(output_dir.join("charlie.json"))?)?;
+    assert_eq!(charlie_content, input_json["charlie"]);
+
+    Ok(())
+}
+
+#[test]
+fn split_depth_round_trip() -> std::io::Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    let input_file = temp_dir.path().join("input.json");
+    let output_dir = temp_dir.path().join("output");
+
+    let input_json = json!({
+        "alpha": {
+            "bravo": {"charlie": {"delta": 1}, "echo": [1, 2]},
+            "foxtrot": "golf"
+        },
+        "hotel": 8
+    });
+    std::fs::write(&input_file, serde_json::to_string_pretty(&input_json)?)?;
+
+    let _ = Command::cargo_bin("jsrmx")
+        .unwrap()
+        .arg("split")
+        .arg("--depth=2")
+        .arg(&input_file)
+        .arg(&output_dir)
+        .assert()
+        .success();
+
+    assert!(output_dir.join("alpha/bravo.json").exists());
+    assert!(output_dir.join("alpha/foxtrot.json").exists());
+    assert!(output_dir.join("hotel.json").exists());
+
+    let bravo_content: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(
+        output_dir.join("alpha/bravo.json"),
+    )?)?;
+    assert_eq!(bravo_content, input_json["alpha"]["bravo"]);
+
+    let output = Command::cargo_bin("jsrmx")
+        .unwrap()
+        .arg("merge")
+        .arg("--recursive")
+        .arg(&output_dir)
+        .arg("-")
+        .output()?;
+    assert!(output.status.success(), "Merge failed: {:?}", output);
+
+    let merged: serde_json::Value = serde_json::from_slice(&output.stdout)?;
+    assert_eq!(merged, input_json);
+
+    Ok(())
+}
+
+#[test]
+fn split_array_round_trip() -> std::io::Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    let input_file = temp_dir.path().join("input.json");
+    let output_dir = temp_dir.path().join("output");
+
+    let input_json = json!([
+        {"id": "alpha", "position": 1},
+        {"id": "bravo", "position": 2},
+        {"id": "charlie", "position": 3}
+    ]);
+    std::fs::write(&input_file, serde_json::to_string_pretty(&input_json)?)?;
+
+    let _ = Command::cargo_bin("jsrmx")
+        .unwrap()
+        .arg("split")
+        .arg(&input_file)
+        .arg(&output_dir)
+        .assert()
+        .success();
+
+    for i in 0..3 {
+        let content: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(
+            output_dir.join(format!("{i:06}.json")),
+        )?)?;
+        assert_eq!(content, input_json[i]);
+    }
+
+    let output = Command::cargo_bin("jsrmx")
+        .unwrap()
+        .arg("merge")
+        .arg("--array")
+        .arg(&output_dir)
+        .arg("-")
+        .output()?;
+    assert!(output.status.success(), "Merge failed: {:?}", output);
+
+    let merged: serde_json::Value = serde_json::from_slice(&output.stdout)?;
+    assert_eq!(merged, input_json);
+
+    Ok(())
+}
+
+#[test]
+fn split_array_by_key_field() -> std::io::Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    let output_dir = temp_dir.path().join("output");
+
+    let input_json = json!([
+        {"id": "alpha", "position": 1},
+        {"id": 2, "position": 2},
+        {"position": 3}
+    ]);
+
+    let _ = Command::cargo_bin("jsrmx")
+        .unwrap()
+        .arg("split")
+        .arg("--key-field=id")
+        .arg("-")
+        .arg(&output_dir)
+        .write_stdin(serde_json::to_string(&input_json)?)
+        .assert()
+        .success();
+
+    assert!(output_dir.join("alpha.json").exists());
+    assert!(output_dir.join("2.json").exists());
+    assert!(output_dir.join("000002.json").exists());
+
+    Ok(())
+}
+
+#[test]
+fn split_at_with_envelope_round_trip() -> std::io::Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    let input_file = temp_dir.path().join("input.json");
+    let envelope_file = temp_dir.path().join("envelope.json");
+    let output_dir = temp_dir.path().join("output");
+
+    let input_json = json!({
+        "meta": {"page": 1},
+        "data": {
+            "count": 2,
+            "items": {
+                "alpha": {"position": 1},
+                "bravo": {"position": 2}
+            }
+        }
+    });
+    std::fs::write(&input_file, serde_json::to_string_pretty(&input_json)?)?;
+
+    let _ = Command::cargo_bin("jsrmx")
+        .unwrap()
+        .arg("split")
+        .arg("--at=data.items")
+        .arg("--envelope")
+        .arg(&envelope_file)
+        .arg(&input_file)
+        .arg(&output_dir)
+        .assert()
+        .success();
+
+    let alpha_content: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(output_dir.join("alpha.json"))?)?;
+    assert_eq!(alpha_content, input_json["data"]["items"]["alpha"]);
+
+    let envelope: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(&envelope_file)?)?;
+    assert_eq!(
+        envelope,
+        json!({
+            "at": "data.items",
+            "document": {"meta": {"page": 1}, "data": {"count": 2, "items": null}}
+        })
+    );
+
+    let output = Command::cargo_bin("jsrmx")
+        .unwrap()
+        .arg("merge")
+        .arg("--envelope")
+        .arg(&envelope_file)
+        .arg(&output_dir)
+        .arg("-")
+        .output()?;
+    assert!(output.status.success(), "Merge failed: {:?}", output);
+
+    let merged: serde_json::Value = serde_json::from_slice(&output.stdout)?;
+    assert_eq!(merged, input_json);
+
+    Ok(())
+}
+
+#[test]
+fn split_at_missing_path_fails() {
+    let _ = Command::cargo_bin("jsrmx")
+        .unwrap()
+        .arg("split")
+        .arg("--at=data.missing")
+        .arg("-")
+        .arg("-")
+        .write_stdin(r#"{"data": {"items": []}}"#)
+        .assert()
+        .failure();
+}
+
+#[test]
+fn split_gzip_input() -> std::io::Result<()> {
+    use std::io::Write;
+
+    let temp_dir = tempfile::tempdir()?;
+    let input_file = temp_dir.path().join("input.json.gz");
+    let output_dir = temp_dir.path().join("output");
+
+    let input_json = json!({
+        "alpha": {"uppercase": "A", "lowercase": "a", "position": 1},
+        "bravo": {"uppercase": "B", "lowercase": "b", "position": 2}
+    });
+    let mut encoder = flate2::write::GzEncoder::new(
+        std::fs::File::create(&input_file)?,
+        flate2::Compression::default(),
+    );
+    encoder.write_all(input_json.to_string().as_bytes())?;
+    encoder.finish()?;
+
+    let _ = Command::cargo_bin("jsrmx")
+        .unwrap()
+        .arg("split")
+        .arg(&input_file)
+        .arg(&output_dir)
+        .assert()
+        .success();
+
+    let alpha_content: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(output_dir.join("alpha.json"))?)?;
+    assert_eq!(alpha_content, input_json["alpha"]);
+
+    Ok(())
+}
+
+#[test]
+fn split_compressed_output() -> std::io::Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    let output_dir = temp_dir.path().join("output");
+
+    let input_json = json!({
+        "alpha": {"uppercase": "A", "lowercase": "a", "position": 1},
+        "bravo": {"uppercase": "B", "lowercase": "b", "position": 2}
+    });
+
+    let _ = Command::cargo_bin("jsrmx")
+        .unwrap()
+        .arg("split")
+        .arg("--compress=zstd:3")
+        .arg("-")
+        .arg(&output_dir)
+        .write_stdin(input_json.to_string())
+        .assert()
+        .success();
+
+    let compressed = std::fs::read(output_dir.join("alpha.json.zst"))?;
+    let alpha_content: serde_json::Value =
+        serde_json::from_slice(&zstd::decode_all(compressed.as_slice())?)?;
+    assert_eq!(alpha_content, input_json["alpha"]);
+
+    // Compressed files merge back under their original names
+    let output = Command::cargo_bin("jsrmx")
+        .unwrap()
+        .arg("merge")
+        .arg(&output_dir)
+        .arg("-")
+        .output()?;
+    let merged: serde_json::Value = serde_json::from_slice(&output.stdout)?;
+    assert_eq!(merged, input_json);
+
+    Ok(())
+}
+
+#[test]
+fn split_rejects_scalar_input() {
+    let _ = Command::cargo_bin("jsrmx")
+        .unwrap()
+        .arg("split")
+        .arg("-")
+        .arg("-")
+        .write_stdin("42")
+        .assert()
+        .failure();
+}
+
+#[test]
+fn split_to_tar_gz_archive() -> std::io::Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    let archive = temp_dir.path().join("letters.tar.gz");
+    let input_json = json!({
+        "alpha": {"uppercase": "A", "lowercase": "a", "position": 1},
+        "bravo": {"uppercase": "B", "lowercase": "b", "position": 2}
+    });
+
+    let _ = Command::cargo_bin("jsrmx")
+        .unwrap()
+        .arg("split")
+        .arg("-")
+        .arg(&archive)
+        .write_stdin(input_json.to_string())
+        .assert()
+        .success();
+
+    let decoder = flate2::read::GzDecoder::new(std::fs::File::open(&archive)?);
+    let mut members = serde_json::Map::new();
+    for entry in tar::Archive::new(decoder).entries()? {
+        let entry = entry?;
+        let name = entry.path()?.to_string_lossy().into_owned();
+        members.insert(name, serde_json::from_reader(entry)?);
+    }
+    assert_eq!(
+        serde_json::Value::Object(members),
+        json!({"alpha.json": input_json["alpha"], "bravo.json": input_json["bravo"]})
+    );
+
+    Ok(())
+}
+
+#[test]
+fn split_to_zip_archive() -> std::io::Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    let archive = temp_dir.path().join("letters.zip");
+    let input_json = json!({
+        "alpha": {"uppercase": "A", "lowercase": "a", "position": 1},
+        "bravo": {"uppercase": "B", "lowercase": "b", "position": 2}
+    });
+
+    let _ = Command::cargo_bin("jsrmx")
+        .unwrap()
+        .arg("split")
+        .arg("-")
+        .arg(&archive)
+        .write_stdin(input_json.to_string())
+        .assert()
+        .success();
+
+    let mut zip = zip::ZipArchive::new(std::fs::File::open(&archive)?)?;
+    assert_eq!(zip.len(), 2);
+    let alpha: serde_json::Value = serde_json::from_reader(zip.by_name("alpha.json")?)?;
+    assert_eq!(alpha, input_json["alpha"]);
+
+    Ok(())
+}
+
+#[test]
+fn split_archive_to_stdout() -> std::io::Result<()> {
+    let input_json = json!({"alpha": {"position": 1}, "bravo": {"position": 2}});
+
+    let output = Command::cargo_bin("jsrmx")
+        .unwrap()
+        .arg("split")
+        .arg("--archive=tar")
+        .arg("-")
+        .arg("-")
+        .write_stdin(input_json.to_string())
+        .output()?;
+    assert!(output.status.success());
+
+    let mut names = tar::Archive::new(output.stdout.as_slice())
+        .entries()?
+        .map(|entry| Ok(entry?.path()?.to_string_lossy().into_owned()))
+        .collect::<std::io::Result<Vec<_>>>()?;
+    names.sort();
+    assert_eq!(names, ["alpha.json", "bravo.json"]);
+
+    Ok(())
+}
+
+#[test]
+fn split_rejects_zip_to_stdout() {
+    let _ = Command::cargo_bin("jsrmx")
+        .unwrap()
+        .arg("split")
+        .arg("--archive=zip")
+        .arg("-")
+        .arg("-")
+        .write_stdin(r#"{"alpha": 1}"#)
+        .assert()
+        .failure();
+}
+
+#[test]
+fn split_archive_rejects_output_path() -> std::io::Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    let output_dir = temp_dir.path().join("output");
+
+    Command::cargo_bin("jsrmx")
+        .unwrap()
+        .arg("split")
+        .arg("--archive=tar")
+        .arg("-")
+        .arg(&output_dir)
+        .write_stdin(r#"{"alpha": 1}"#)
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("--archive"));
+    assert!(!output_dir.exists());
+
+    Ok(())
+}
+
+#[test]
+fn split_name_template() -> std::io::Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    let output_dir = temp_dir.path().join("output");
+    let input_json = json!([
+        {"id": 7, "type": "dashboard", "title": "Web Traffic"},
+        {"id": 8, "type": "search"}
+    ]);
+
+    let _ = Command::cargo_bin("jsrmx")
+        .unwrap()
+        .arg("split")
+        .arg("--name-template={type}/{title,@key|slug}-{id}")
+        .arg("-")
+        .arg(&output_dir)
+        .write_stdin(input_json.to_string())
+        .assert()
+        .success();
+
+    let dashboard: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(
+        output_dir.join("dashboard/web-traffic-7.json"),
+    )?)?;
+    assert_eq!(dashboard, input_json[0]);
+    assert!(output_dir.join("search/000001-8.json").exists());
+
+    Ok(())
+}
+
+#[test]
+fn split_unsafe_keys_round_trip() -> std::io::Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    let output_dir = temp_dir.path().join("output");
+    let input_json = json!({"a/b": 1, "..": 2, ".hidden": 3, "100%": 4, "C:\\temp": 5});
+
+    let _ = Command::cargo_bin("jsrmx")
+        .unwrap()
+        .arg("split")
+        .arg("-")
+        .arg(&output_dir)
+        .write_stdin(input_json.to_string())
+        .assert()
+        .success();
+
+    assert!(output_dir.join("a%2Fb.json").is_file());
+    assert!(output_dir.join("%2E..json").is_file());
+    assert!(!temp_dir.path().join("..json").exists());
+
+    let output = Command::cargo_bin("jsrmx")
+        .unwrap()
+        .arg("merge")
+        .arg(&output_dir)
+        .arg("-")
+        .output()?;
+    let merged: serde_json::Value = serde_json::from_slice(&output.stdout)?;
+    assert_eq!(merged, input_json);
+
+    Ok(())
+}
+
+#[test]
+fn split_refuses_colliding_keys() -> std::io::Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    let output_dir = temp_dir.path().join("output");
+
+    let output = Command::cargo_bin("jsrmx")
+        .unwrap()
+        .args(["split", "--key-field=id", "-"])
+        .arg(&output_dir)
+        .write_stdin(r#"{"alpha": {"id": "x"}, "bravo": {"id": "y"}, "charlie": {"id": "x"}}"#)
+        .output()?;
+    assert!(!output.status.success());
+    // The collision is reported as it is, not as a parse error at some line and column
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("x"), "{stderr}");
+    assert!(!stderr.contains("column"), "{stderr}");
+
+    // Entries before the collision stay written, and the first is kept instead of being overwritten
+    let files = std::fs::read_dir(&output_dir)?.count();
+    assert_eq!(files, 2);
+    let alpha: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(output_dir.join("x.json"))?)?;
+    assert_eq!(alpha, json!({"id": "x"}));
+
+    // Keys that only differ by case only collide where the filesystem ignores case
+    let output_dir = temp_dir.path().join("cased");
+    let split = Command::cargo_bin("jsrmx")
+        .unwrap()
+        .args(["split", "-"])
+        .arg(&output_dir)
+        .write_stdin(r#"{"Alpha": 1, "alpha": 2}"#)
+        .assert();
+    match jsrmx::filename::is_case_insensitive(temp_dir.path()) {
+        true => split.failure(),
+        false => split.success(),
+    };
+    let alpha: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(output_dir.join("Alpha.json"))?)?;
+    assert_eq!(alpha, json!(1));
+
+    Ok(())
+}
+
 // TODO: The --compact option is not yet implemented for the split command
 // #[test]
 // fn split_compact() -> std::io::Result<()> {
@@ -134,3 +661,224 @@ fn split_with_drop() -> std::io::Result<()> {
 //     // Check that only filtered keys are in the output
 //     Ok(())
 // }
+
+#[test]
+fn split_manifest_restores_order() -> std::io::Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    let input = r#"{"charlie":{"position":1},"alpha":{"position":2},"bravo":{"position":3}}"#;
+    let merge = |input: &std::path::Path| {
+        Command::cargo_bin("jsrmx")
+            .unwrap()
+            .args(["merge", "--compact", input.to_str().unwrap(), "-"])
+            .output()
+    };
+
+    for output in ["output", "output.tar"] {
+        let output = temp_dir.path().join(output);
+        Command::cargo_bin("jsrmx")
+            .unwrap()
+            .arg("split")
+            .arg("--manifest")
+            .arg("-")
+            .arg(&output)
+            .write_stdin(input)
+            .assert()
+            .success();
+
+        let merged = merge(&output)?;
+        assert!(merged.status.success(), "{merged:?}");
+        assert_eq!(String::from_utf8_lossy(&merged.stdout).trim(), input);
+    }
+
+    let output_dir = temp_dir.path().join("output");
+    let manifest: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(output_dir.join("_manifest.json"))?)?;
+    assert_eq!(manifest["entries"][1]["key"], "alpha");
+    assert_eq!(manifest["entries"][1]["path"], "alpha.json");
+    assert_eq!(manifest["entries"][1]["position"], 1);
+
+    std::fs::write(output_dir.join("alpha.json"), r#"{"position":20}"#)?;
+    let merged = merge(&output_dir)?;
+    assert!(!merged.status.success());
+    assert!(String::from_utf8_lossy(&merged.stderr).contains("Checksum mismatch for alpha.json"));
+
+    Ok(())
+}
+
+#[test]
+fn split_manifest_restores_original_keys() -> std::io::Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    let output_dir = temp_dir.path().join("output");
+    let input = r#"{"charlie":{"id":"c","data":{"x":1}},"alpha":{"id":"a","data":{"y":2}}}"#;
+
+    Command::cargo_bin("jsrmx")
+        .unwrap()
+        .args(["split", "--manifest", "--key-field=id", "--depth=2", "-"])
+        .arg(&output_dir)
+        .write_stdin(input)
+        .assert()
+        .success();
+    assert!(output_dir.join("c/data.json").is_file());
+
+    let manifest: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(output_dir.join("_manifest.json"))?)?;
+    let entry = manifest["entries"]
+        .as_array()
+        .and_then(|entries| entries.iter().find(|entry| entry["path"] == "c/data.json"))
+        .expect("c/data.json is missing from the manifest");
+    assert_eq!(entry["key"], "charlie");
+    assert_eq!(entry["nested"], json!(["data"]));
+
+    let merged = Command::cargo_bin("jsrmx")
+        .unwrap()
+        .args(["merge", "--compact", "--recursive"])
+        .arg(&output_dir)
+        .arg("-")
+        .output()?;
+    assert!(merged.status.success(), "{merged:?}");
+    let merged: serde_json::Value = serde_json::from_slice(&merged.stdout)?;
+    assert_eq!(merged, serde_json::from_str::<serde_json::Value>(input)?);
+
+    Ok(())
+}
+
+#[test]
+fn split_usage_error_does_not_wait_for_stdin() -> std::io::Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    let mut child = std::process::Command::new(assert_cmd::cargo::cargo_bin("jsrmx"))
+        .args(["split", "--archive=tar", "-"])
+        .arg(temp_dir.path().join("output"))
+        .stdin(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::null())
+        .spawn()?;
+
+    // Stdin stays open and empty, so the command would block if it read it before failing
+    let _stdin = child.stdin.take();
+    let started = std::time::Instant::now();
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        if started.elapsed() > std::time::Duration::from_secs(10) {
+            child.kill()?;
+            panic!("split waited for stdin before reporting a usage error");
+        }
+        std::thread::sleep(std::time::Duration::from_millis(20));
+    };
+    assert!(!status.success());
+
+    Ok(())
+}
+
+#[cfg(feature = "arbitrary_precision")]
+#[test]
+fn split_canonical_rejects_numbers_beyond_doubles() {
+    let output = Command::cargo_bin("jsrmx")
+        .unwrap()
+        .args(["--canonical", "split", "-", "-"])
+        .write_stdin(r#"{"alpha":{"n":1e400}}"#)
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("1e400 is out of range"));
+}
+
+#[test]
+fn split_with_format_options() -> std::io::Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    let output_dir = temp_dir.path().join("output");
+
+    Command::cargo_bin("jsrmx")
+        .unwrap()
+        .args([
+            "split",
+            "--indent=tab",
+            "--inline-width=30",
+            "--ensure-ascii",
+        ])
+        .arg("-")
+        .arg(&output_dir)
+        .write_stdin(
+            r#"{"alpha":{"name":"café","notes":["a long note that won't fit"],"tags":["a","b"]}}"#,
+        )
+        .assert()
+        .success();
+
+    assert_eq!(
+        std::fs::read_to_string(output_dir.join("alpha.json"))?,
+        "{\n\t\"name\": \"caf\\u00e9\",\n\t\"notes\": [\n\t\t\"a long note that won't fit\"\n\t],\n\t\"tags\": [\"a\", \"b\"]\n}\n"
+    );
+
+    Command::cargo_bin("jsrmx")
+        .unwrap()
+        .args(["split", "--compact", "--no-trailing-newline", "-"])
+        .arg(&output_dir)
+        .write_stdin(r#"{"bravo":{"name":"b"}}"#)
+        .assert()
+        .success();
+    assert_eq!(
+        std::fs::read_to_string(output_dir.join("bravo.json"))?,
+        r#"{"name":"b"}"#
+    );
+
+    Ok(())
+}
+
+#[test]
+fn split_canonical_is_byte_stable() -> std::io::Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+
+    for (dir, input) in [
+        ("first", r#"{"alpha":{"b":1.0,"a":"é","c":[1e21,0.5]}}"#),
+        (
+            "second",
+            r#"{"alpha":{"c":[1000000000000000000000,5e-1],"a":"é","b":1}}"#,
+        ),
+    ] {
+        Command::cargo_bin("jsrmx")
+            .unwrap()
+            .args(["split", "--canonical", "--indent=4", "-"])
+            .arg(temp_dir.path().join(dir))
+            .write_stdin(input)
+            .assert()
+            .success();
+    }
+
+    let first = std::fs::read(temp_dir.path().join("first/alpha.json"))?;
+    assert_eq!(first, r#"{"a":"é","b":1,"c":[1e+21,0.5]}"#.as_bytes());
+    assert_eq!(
+        first,
+        std::fs::read(temp_dir.path().join("second/alpha.json"))?
+    );
+
+    Ok(())
+}
+
+#[test]
+fn split_keep_fields() -> std::io::Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    let output_dir = temp_dir.path().join("output");
+
+    Command::cargo_bin("jsrmx")
+        .unwrap()
+        .args(["split", "--compact", "--keep=id,type,attributes.title", "-"])
+        .arg(&output_dir)
+        .write_stdin(
+            r#"{"alpha":{"type":"doc","id":1,"attributes":{"body":"b","title":"t"},"refs":[]}}"#,
+        )
+        .assert()
+        .success();
+
+    let alpha = std::fs::read_to_string(output_dir.join("alpha.json"))?;
+    assert_eq!(
+        serde_json::from_str::<serde_json::Value>(&alpha)?,
+        json!({"type": "doc", "id": 1, "attributes": {"title": "t"}})
+    );
+    #[cfg(feature = "preserve_order")]
+    assert_eq!(
+        alpha,
+        "{\"type\":\"doc\",\"id\":1,\"attributes\":{\"title\":\"t\"}}\n"
+    );
+
+    Ok(())
+}
@@ -29,7 +29,7 @@ fn test_unbundle_command() -> std::io::Result<()> {
     let input_dir = tempdir()?;
     let output_dir = tempdir()?;
 
-    let (input_content, input_file) = create_input_file(&input_dir.path())?;
+    let (input_content, input_file) = create_input_file(input_dir.path())?;
 
     // Test 1: Basic unbundle
     let output = Command::cargo_bin("jsrmx")
@@ -85,7 +85,7 @@ fn test_unbundle_with_name() -> std::io::Result<()> {
     let input_dir = tempdir()?;
     let output_dir = tempdir()?;
 
-    let (input_content, input_file) = create_input_file(&input_dir.path())?;
+    let (input_content, input_file) = create_input_file(input_dir.path())?;
 
     // Test 2: Unbundle with --name option
     let output = Command::cargo_bin("jsrmx")
@@ -132,7 +132,7 @@ fn test_unbundle_with_drop_fields() -> std::io::Result<()> {
     let input_dir = tempdir()?;
     let output_dir = tempdir()?;
 
-    let (input_content, input_file) = create_input_file(&input_dir.path())?;
+    let (input_content, input_file) = create_input_file(input_dir.path())?;
 
     // Check that the --drop argument removes specified fields
     let output = Command::cargo_bin("jsrmx")
@@ -178,4 +178,634 @@ fn test_unbundle_with_drop_fields() -> std::io::Result<()> {
     Ok(())
 }
 
+#[test]
+fn test_unbundle_records_at() -> std::io::Result<()> {
+    let input_dir = tempdir()?;
+    let output_dir = tempdir()?;
+
+    let (input_content, _) = create_input_file(input_dir.path())?;
+    let input_file = input_dir.path().join("letters.json");
+    let document = json!({"took": 3, "hits": {"total": 3, "hits": input_content}});
+    fs::write(&input_file, document.to_string())?;
+
+    let output = Command::cargo_bin("jsrmx")
+        .unwrap()
+        .arg("unbundle")
+        .arg("--name=name")
+        .arg("--records-at=hits.hits")
+        .arg(&input_file)
+        .arg(output_dir.path())
+        .output()?;
+
+    assert!(
+        output.status.success(),
+        "Unbundle command with --records-at failed: {:?}",
+        output
+    );
+
+    for entry in &input_content {
+        let file_name = format!("{}.json", entry["name"].as_str().unwrap());
+        let file_content: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(output_dir.path().join(&file_name))?)?;
+        assert_eq!(&file_content, entry);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_unbundle_zstd_stdin() -> std::io::Result<()> {
+    let input_dir = tempdir()?;
+    let output_dir = tempdir()?;
+
+    let (input_content, input_file) = create_input_file(input_dir.path())?;
+    let compressed = zstd::encode_all(fs::read(&input_file)?.as_slice(), 0)?;
+
+    let output = Command::cargo_bin("jsrmx")
+        .unwrap()
+        .arg("unbundle")
+        .arg("--name=name")
+        .arg("-")
+        .arg(output_dir.path())
+        .write_stdin(compressed)
+        .output()?;
+
+    assert!(
+        output.status.success(),
+        "Unbundle command with compressed stdin failed: {:?}",
+        output
+    );
+
+    for entry in &input_content {
+        let file_name = format!("{}.json", entry["name"].as_str().unwrap());
+        let file_content: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(output_dir.path().join(&file_name))?)?;
+        assert_eq!(&file_content, entry);
+    }
+
+    Ok(())
+}
+
 // TODO: Add tests for the --compact option
+
+#[test]
+fn test_unbundle_to_tar_archive() -> std::io::Result<()> {
+    let input_dir = tempdir()?;
+    let (input_content, input_file) = create_input_file(input_dir.path())?;
+    let archive = input_dir.path().join("letters.tar");
+
+    Command::cargo_bin("jsrmx")
+        .unwrap()
+        .args([
+            "unbundle",
+            input_file.to_str().unwrap(),
+            archive.to_str().unwrap(),
+            "--name=name",
+        ])
+        .assert()
+        .success();
+
+    let mut members = Vec::new();
+    for entry in tar::Archive::new(fs::File::open(&archive)?).entries()? {
+        let entry = entry?;
+        let name = entry.path()?.to_string_lossy().into_owned();
+        members.push((name, serde_json::from_reader::<_, Value>(entry)?));
+    }
+    members.sort_by(|a, b| a.0.cmp(&b.0));
+    let expected = ["alpha", "bravo", "charlie"]
+        .iter()
+        .zip(input_content)
+        .map(|(name, value)| (format!("{name}.json"), value))
+        .collect::<Vec<_>>();
+    assert_eq!(members, expected);
+
+    Ok(())
+}
+
+#[test]
+fn test_unbundle_skips_empty_names() -> std::io::Result<()> {
+    let input_dir = tempdir()?;
+    let output_dir = tempdir()?;
+    let input_file = input_dir.path().join("records.ndjson");
+    fs::write(
+        &input_file,
+        "{\"name\":\"\",\"id\":7}\n{\"name\":\"\"}\n{\"name\":\"alpha\"}\n",
+    )?;
+
+    Command::cargo_bin("jsrmx")
+        .unwrap()
+        .args(["unbundle", "--name=name,id"])
+        .arg(&input_file)
+        .arg(output_dir.path())
+        .assert()
+        .success();
+
+    let mut files = fs::read_dir(output_dir.path())?
+        .map(|entry| entry.map(|entry| entry.file_name().to_string_lossy().into_owned()))
+        .collect::<std::io::Result<Vec<_>>>()?;
+    files.sort();
+    assert_eq!(files, ["7.json", "alpha.json", "object-000001.json"]);
+
+    Ok(())
+}
+
+#[test]
+fn test_unbundle_name_template() -> std::io::Result<()> {
+    let input_dir = tempdir()?;
+    let output_dir = tempdir()?;
+    let (input_content, input_file) = create_input_file(input_dir.path())?;
+
+    Command::cargo_bin("jsrmx")
+        .unwrap()
+        .args([
+            "unbundle",
+            input_file.to_str().unwrap(),
+            output_dir.path().to_str().unwrap(),
+            "--name-template={letter.uppercase}/{name|upper}-{position|pad:3}.json",
+        ])
+        .assert()
+        .success();
+
+    for (path, expected) in ["A/ALPHA-001", "B/BRAVO-002", "C/CHARLIE-003"]
+        .iter()
+        .zip(&input_content)
+    {
+        let content = fs::read_to_string(output_dir.path().join(format!("{path}.json")))?;
+        assert_eq!(&serde_json::from_str::<Value>(&content)?, expected);
+    }
+
+    // Names are rendered before fields are dropped or renamed
+    let renamed_dir = tempdir()?;
+    Command::cargo_bin("jsrmx")
+        .unwrap()
+        .args([
+            "--drop=position",
+            "--rename=name=title",
+            "unbundle",
+            input_file.to_str().unwrap(),
+            renamed_dir.path().to_str().unwrap(),
+            "--name-template={name}-{position}",
+        ])
+        .assert()
+        .success();
+    let content = fs::read_to_string(renamed_dir.path().join("alpha-1.json"))?;
+    let record: Value = serde_json::from_str(&content)?;
+    assert_eq!(record["title"], "alpha");
+    assert!(record.get("position").is_none());
+
+    // A missing field without a fallback fails instead of writing an empty name
+    Command::cargo_bin("jsrmx")
+        .unwrap()
+        .args([
+            "unbundle",
+            input_file.to_str().unwrap(),
+            "-",
+            "--name-template={missing}",
+        ])
+        .assert()
+        .failure();
+
+    Ok(())
+}
+
+#[test]
+fn test_unbundle_numeric_name() -> std::io::Result<()> {
+    let input_dir = tempdir()?;
+    let output_dir = tempdir()?;
+    let (_, input_file) = create_input_file(input_dir.path())?;
+
+    Command::cargo_bin("jsrmx")
+        .unwrap()
+        .args([
+            "unbundle",
+            input_file.to_str().unwrap(),
+            output_dir.path().to_str().unwrap(),
+            "--name=position",
+        ])
+        .assert()
+        .success();
+
+    assert!(output_dir.path().join("1.json").exists());
+    assert!(output_dir.path().join("3.json").exists());
+
+    Ok(())
+}
+
+#[test]
+fn test_unbundle_refuses_escaping_names() -> std::io::Result<()> {
+    let input_dir = tempdir()?;
+    let output_dir = input_dir.path().join("output");
+    let (_, input_file) = create_input_file(input_dir.path())?;
+
+    Command::cargo_bin("jsrmx")
+        .unwrap()
+        .args([
+            "unbundle",
+            input_file.to_str().unwrap(),
+            output_dir.to_str().unwrap(),
+            "--name-template=../{name}",
+        ])
+        .assert()
+        .failure();
+    assert!(!input_dir.path().join("alpha.json").exists());
+
+    // A `/` inside a field value is encoded instead of creating a directory
+    let input_file = input_dir.path().join("slashes.ndjson");
+    fs::write(&input_file, "{\"name\":\"../escape\"}\n")?;
+    Command::cargo_bin("jsrmx")
+        .unwrap()
+        .args([
+            "unbundle",
+            input_file.to_str().unwrap(),
+            output_dir.to_str().unwrap(),
+            "--name=name",
+        ])
+        .assert()
+        .success();
+    assert!(output_dir.join("%2E.%2Fescape.json").is_file());
+
+    Ok(())
+}
+
+#[test]
+fn test_unbundle_on_collision() -> std::io::Result<()> {
+    let input_dir = tempdir()?;
+    let input_file = input_dir.path().join("duplicates.ndjson");
+    fs::write(
+        &input_file,
+        "{\"name\":\"alpha\",\"position\":1}\n{\"name\":\"alpha\",\"position\":2}\n{\"name\":\"Alpha\",\"position\":3}\n",
+    )?;
+    let unbundle = |policy: &str, output_dir: &std::path::Path| {
+        Command::cargo_bin("jsrmx")
+            .unwrap()
+            .args([
+                "unbundle",
+                input_file.to_str().unwrap(),
+                output_dir.to_str().unwrap(),
+                "--name=name",
+                &format!("--on-collision={policy}"),
+            ])
+            .assert()
+    };
+    let position = |path: std::path::PathBuf| -> std::io::Result<Value> {
+        Ok(serde_json::from_str::<Value>(&fs::read_to_string(path)?)?["position"].clone())
+    };
+
+    let output_dir = input_dir.path().join("error");
+    unbundle("error", &output_dir).failure();
+    assert_eq!(position(output_dir.join("alpha.json"))?, 1);
+
+    // `Alpha` only collides with `alpha` where the filesystem ignores case
+    let folds_case = jsrmx::filename::is_case_insensitive(input_dir.path());
+    let output_dir = input_dir.path().join("suffix");
+    unbundle("suffix", &output_dir)
+        .success()
+        .stderr(predicates::str::contains(match folds_case {
+            true => "2 name collisions",
+            false => "1 name collision",
+        }));
+    assert_eq!(position(output_dir.join("alpha.json"))?, 1);
+    assert_eq!(position(output_dir.join("alpha-1.json"))?, 2);
+    match folds_case {
+        true => assert_eq!(position(output_dir.join("Alpha-2.json"))?, 3),
+        false => assert_eq!(position(output_dir.join("Alpha.json"))?, 3),
+    }
+
+    let output_dir = input_dir.path().join("skip");
+    unbundle("skip", &output_dir).success();
+    assert_eq!(fs::read_dir(&output_dir)?.count(), 1 + !folds_case as usize);
+    assert_eq!(position(output_dir.join("alpha.json"))?, 1);
+
+    let output_dir = input_dir.path().join("overwrite");
+    unbundle("overwrite", &output_dir).success();
+    assert_eq!(position(output_dir.join("alpha.json"))?, 2);
+
+    Ok(())
+}
+
+#[test]
+fn unbundle_manifest_bundle_round_trip() -> std::io::Result<()> {
+    let input_dir = tempdir()?;
+    let output_dir = input_dir.path().join("output");
+    let input = [
+        json!({"name": "charlie", "position": 1}),
+        json!({"name": "alpha", "position": 2}),
+        json!({"name": "bravo", "position": 3}),
+    ]
+    .iter()
+    .map(|record| record.to_string() + "\n")
+    .collect::<String>();
+    let input_file = input_dir.path().join("records.ndjson");
+    fs::write(&input_file, &input)?;
+
+    Command::cargo_bin("jsrmx")
+        .unwrap()
+        .args(["unbundle", "--manifest", "--name=name"])
+        .arg(&input_file)
+        .arg(&output_dir)
+        .assert()
+        .success();
+    assert!(output_dir.join("_manifest.json").is_file());
+
+    let output = Command::cargo_bin("jsrmx")
+        .unwrap()
+        .arg("bundle")
+        .arg(&output_dir)
+        .output()?;
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(String::from_utf8_lossy(&output.stdout), input);
+
+    Ok(())
+}
+
+#[test]
+fn bundle_fails_on_files_changed_since_manifest() -> std::io::Result<()> {
+    let input_dir = tempdir()?;
+    let output_dir = input_dir.path().join("output");
+    let input_file = input_dir.path().join("records.ndjson");
+    fs::write(&input_file, "{\"name\":\"alpha\"}\n{\"name\":\"bravo\"}\n")?;
+
+    Command::cargo_bin("jsrmx")
+        .unwrap()
+        .args(["unbundle", "--manifest", "--name=name"])
+        .arg(&input_file)
+        .arg(&output_dir)
+        .assert()
+        .success();
+    fs::write(output_dir.join("bravo.json"), r#"{"name":"tampered"}"#)?;
+
+    let output = Command::cargo_bin("jsrmx")
+        .unwrap()
+        .arg("bundle")
+        .arg(&output_dir)
+        .output()?;
+    assert_eq!(output.status.code(), Some(1), "{output:?}");
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Checksum mismatch for bravo.json"));
+
+    Ok(())
+}
+
+#[test]
+#[cfg(feature = "preserve_order")]
+fn unbundle_bundle_keeps_key_order() -> std::io::Result<()> {
+    let input_dir = tempdir()?;
+    let output_dir = input_dir.path().join("output");
+    let input_file = input_dir.path().join("records.ndjson");
+    fs::write(
+        &input_file,
+        "{\"zulu\":1,\"alpha\":{\"yankee\":2,\"bravo\":3},\"mike\":4}\n",
+    )?;
+
+    Command::cargo_bin("jsrmx")
+        .unwrap()
+        .arg("unbundle")
+        .arg(&input_file)
+        .arg(&output_dir)
+        .assert()
+        .success();
+    let file = fs::read_to_string(output_dir.join("object-000000.json"))?;
+    assert!(file.find("zulu") < file.find("alpha"));
+    assert!(file.find("yankee") < file.find("bravo"));
+
+    let output = Command::cargo_bin("jsrmx")
+        .unwrap()
+        .arg("bundle")
+        .arg(&output_dir)
+        .arg("--drop=alpha.yankee")
+        .output()?;
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "{\"zulu\":1,\"alpha\":{\"bravo\":3},\"mike\":4}\n"
+    );
+
+    Ok(())
+}
+
+#[test]
+#[cfg(feature = "arbitrary_precision")]
+fn unbundle_bundle_keeps_number_literals() -> std::io::Result<()> {
+    let input_dir = tempdir()?;
+    let output_dir = input_dir.path().join("output");
+    let input_file = input_dir.path().join("records.ndjson");
+    // Keys in alphabetical order, so the record is the same with or without `preserve_order`
+    let record = "{\"cost\":0.10000000000000000000001,\"id\":123456789012345678901234567890,\"rate\":1.0,\"total\":1e3}\n";
+    fs::write(&input_file, record)?;
+
+    Command::cargo_bin("jsrmx")
+        .unwrap()
+        .args(["unbundle", "--compact"])
+        .arg(&input_file)
+        .arg(&output_dir)
+        .assert()
+        .success();
+    assert_eq!(
+        fs::read_to_string(output_dir.join("object-000000.json"))?,
+        record
+    );
+
+    let output = Command::cargo_bin("jsrmx")
+        .unwrap()
+        .arg("bundle")
+        .arg(&output_dir)
+        .output()?;
+    assert_eq!(String::from_utf8_lossy(&output.stdout), record);
+
+    Ok(())
+}
+
+#[test]
+fn unbundle_field_paths() -> std::io::Result<()> {
+    let input_dir = tempdir()?;
+    let output_dir = input_dir.path().join("output");
+    let input_file = input_dir.path().join("objects.ndjson");
+    fs::write(
+        &input_file,
+        concat!(
+            r#"{"meta":{"a.b":"first"},"objects":[{"attributes":{"_secret":1,"nested":{"_secret":2,"keep":3}}},{"attributes":{"keep":4}}]}"#,
+            "\n",
+        ),
+    )?;
+
+    Command::cargo_bin("jsrmx")
+        .unwrap()
+        .args([
+            "unbundle",
+            "--compact",
+            "--drop",
+            "objects[*].attributes.**._secret",
+            r#"--name=meta."a.b""#,
+        ])
+        .arg(&input_file)
+        .arg(&output_dir)
+        .assert()
+        .success();
+    assert_eq!(
+        fs::read_to_string(output_dir.join("first.json"))?,
+        concat!(
+            r#"{"meta":{"a.b":"first"},"objects":[{"attributes":{"nested":{"keep":3}}},{"attributes":{"keep":4}}]}"#,
+            "\n",
+        )
+    );
+
+    let output = Command::cargo_bin("jsrmx")
+        .unwrap()
+        .args([
+            "bundle",
+            "--escape",
+            "objects[*]./^attr/",
+            "--drop",
+            r"meta.a\.b",
+        ])
+        .arg(&output_dir)
+        .output()?;
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        concat!(
+            r#"{"meta":{},"objects":[{"attributes":"{\"nested\":{\"keep\":3}}"},{"attributes":"{\"keep\":4}"}]}"#,
+            "\n",
+        )
+    );
+
+    Command::cargo_bin("jsrmx")
+        .unwrap()
+        .args(["unbundle", "--drop", "objects[x]"])
+        .arg(&input_file)
+        .arg(&output_dir)
+        .assert()
+        .failure();
+
+    Ok(())
+}
+
+#[test]
+fn unbundle_redact_and_pseudonymize() -> std::io::Result<()> {
+    let input_dir = tempdir()?;
+    let output_dir = input_dir.path().join("output");
+    let input_file = input_dir.path().join("users.ndjson");
+    fs::write(
+        &input_file,
+        concat!(
+            r#"{"id":1,"email":"a@example.com","contacts":[{"phone":"1"},{"phone":"2"}]}"#,
+            "\n",
+            r#"{"id":2,"email":"a@example.com","contacts":[]}"#,
+            "\n",
+        ),
+    )?;
+
+    Command::cargo_bin("jsrmx")
+        .unwrap()
+        .args(["unbundle", "--name=id", "--redact=contacts[*].phone"])
+        .args(["--pseudonymize=email", "--key=secret"])
+        .arg(&input_file)
+        .arg(&output_dir)
+        .assert()
+        .success();
+
+    let read = |name: &str| -> std::io::Result<Value> {
+        Ok(serde_json::from_str(&fs::read_to_string(
+            output_dir.join(name),
+        )?)?)
+    };
+    let (first, second) = (read("1.json")?, read("2.json")?);
+    assert_eq!(
+        first["contacts"],
+        json!([{"phone": "[REDACTED]"}, {"phone": "[REDACTED]"}])
+    );
+    assert_eq!(first["email"], second["email"]);
+    assert_ne!(first["email"], "a@example.com");
+
+    // The key can come from the environment or a file instead of the command line
+    let key_file = input_dir.path().join("key.txt");
+    fs::write(&key_file, "secret\n")?;
+    let from_env = input_dir.path().join("from_env");
+    let from_file = input_dir.path().join("from_file");
+    Command::cargo_bin("jsrmx")
+        .unwrap()
+        .env("JSRMX_KEY", "secret")
+        .args(["unbundle", "--name=id", "--pseudonymize=email"])
+        .arg(&input_file)
+        .arg(&from_env)
+        .assert()
+        .success();
+    Command::cargo_bin("jsrmx")
+        .unwrap()
+        .env_remove("JSRMX_KEY")
+        .args([
+            "unbundle",
+            "--name=id",
+            "--pseudonymize=email",
+            "--key-file",
+        ])
+        .arg(&key_file)
+        .arg(&input_file)
+        .arg(&from_file)
+        .assert()
+        .success();
+    for dir in [from_env, from_file] {
+        let other: Value = serde_json::from_str(&fs::read_to_string(dir.join("1.json"))?)?;
+        assert_eq!(other["email"], first["email"]);
+    }
+
+    Command::cargo_bin("jsrmx")
+        .unwrap()
+        .env_remove("JSRMX_KEY")
+        .args(["unbundle", "--pseudonymize=email"])
+        .arg(&input_file)
+        .arg(&output_dir)
+        .assert()
+        .failure();
+
+    Ok(())
+}
+
+#[test]
+fn unbundle_set_from_context() -> std::io::Result<()> {
+    let input_dir = tempdir()?;
+    let output_dir = input_dir.path().join("output");
+    let input_file = input_dir.path().join("records.ndjson");
+    // Blank and unparsable lines still count towards the line numbers
+    fs::write(&input_file, "{\"id\":\"a\"}\n\nnot json\n{\"id\":\"b\"}\n")?;
+
+    Command::cargo_bin("jsrmx")
+        .unwrap()
+        .args(["unbundle", "--compact", "--name=id"])
+        .args(["--set-from=_file=filename,_line=index,_name=key,_at=now"])
+        .arg(&input_file)
+        .arg(&output_dir)
+        .assert()
+        .success();
+
+    let read = |dir: &std::path::Path, name: &str| -> std::io::Result<Value> {
+        Ok(serde_json::from_str(&fs::read_to_string(dir.join(name))?)?)
+    };
+    assert_eq!(read(&output_dir, "a.json")?["_line"], 1);
+    let record = read(&output_dir, "b.json")?;
+    assert_eq!(record["_file"], "records.ndjson");
+    assert_eq!(record["_line"], 4);
+    assert_eq!(record["_name"], "b");
+    assert!(record["_at"].as_str().is_some_and(|at| at.ends_with('Z')));
+
+    // With `--records-at`, `index` is the position in the array
+    let document = input_dir.path().join("document.json");
+    fs::write(&document, r#"{"records": [{"id": "a"}, {"id": "b"}]}"#)?;
+    let records_dir = input_dir.path().join("records");
+    Command::cargo_bin("jsrmx")
+        .unwrap()
+        .args(["unbundle", "--name=id", "--records-at=records"])
+        .args(["--set-from=_index=index"])
+        .arg(&document)
+        .arg(&records_dir)
+        .assert()
+        .success();
+    assert_eq!(read(&records_dir, "b.json")?["_index"], 1);
+
+    Command::cargo_bin("jsrmx")
+        .unwrap()
+        .args(["unbundle", "--set=env=prod"])
+        .arg(&input_file)
+        .arg(&output_dir)
+        .assert()
+        .failure();
+
+    Ok(())
+}